@@ -0,0 +1,133 @@
+use bevy::prelude::*;
+use chessai::{position, util};
+use rand::Rng;
+
+// 开局库: bincode序列化的(局面校验码, 走法, 权重)列表, 按校验码升序排列
+const BOOK_DATA: &[u8] = include_bytes!("../book.dat");
+
+/// 开局库走法
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BookMove {
+    // ICCS走法
+    pub iccs: String,
+    // 权重, 越大越常用
+    pub weight: usize,
+}
+
+/// 开局库, 以局面的zobrist校验码为键
+#[derive(Resource)]
+pub struct Book {
+    items: Vec<(u64, u64, u64)>,
+}
+
+impl Book {
+    pub fn new() -> Self {
+        let items: Vec<(u64, u64, u64)> = bincode::deserialize(BOOK_DATA).unwrap();
+        info!("加载开局库 {}条", items.len());
+        Self { items }
+    }
+
+    /// 查询引擎当前局面的开局库走法, 局面不在库中时查询左右镜像局面
+    pub fn probe(&self, engine: &chessai::Engine) -> Vec<BookMove> {
+        let (items, mirror) = match self.find(lock(engine)) {
+            items if !items.is_empty() => (items, false),
+            _ => (self.find(lock(&engine.mirror())), true),
+        };
+        items
+            .iter()
+            .map(|&(_, mv, weight)| {
+                let mv = if mirror {
+                    util::mirror_move(mv as isize)
+                } else {
+                    mv as isize
+                };
+                BookMove {
+                    iccs: position::move2iccs(mv),
+                    weight: weight as usize,
+                }
+            })
+            .collect()
+    }
+
+    /// 按权重随机选择引擎当前局面的合法开局库走法
+    pub fn pick(&self, engine: &chessai::Engine) -> Option<String> {
+        let moves: Vec<BookMove> = self
+            .probe(engine)
+            .into_iter()
+            .filter(|book_move| engine.legal_move(position::iccs2move(&book_move.iccs)))
+            .collect();
+        let total: usize = moves.iter().map(|book_move| book_move.weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut value = rand::thread_rng().gen_range(0..total);
+        for book_move in moves {
+            if value < book_move.weight {
+                return Some(book_move.iccs);
+            }
+            value -= book_move.weight;
+        }
+        None
+    }
+
+    fn find(&self, lock: u64) -> &[(u64, u64, u64)] {
+        let start = self.items.partition_point(|item| item.0 < lock);
+        let end = self.items.partition_point(|item| item.0 <= lock);
+        &self.items[start..end]
+    }
+}
+
+/// 开局库的键: 引擎zobrist校验码的低32位右移一位
+fn lock(engine: &chessai::Engine) -> u64 {
+    util::unsigned_right_shift(engine.zobrist_lock, 1) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::public::START_POS;
+
+    fn engine(fen: &str) -> chessai::Engine {
+        let mut engine = chessai::Engine::new();
+        engine.from_fen(fen);
+        engine
+    }
+
+    #[test]
+    fn test_start_pos() {
+        let moves = Book::new().probe(&engine(START_POS));
+        assert_eq!(moves.len(), 16);
+        // 当头炮
+        assert!(moves.contains(&BookMove {
+            iccs: String::from("h2e2"),
+            weight: 1473
+        }));
+        assert!(moves.contains(&BookMove {
+            iccs: String::from("c3c4"),
+            weight: 546
+        }));
+    }
+
+    #[test]
+    fn test_mirror() {
+        // 炮二平五后的局面只以镜像形式收录
+        let fen = "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C2C4/9/RNBAKABNR b - - 0 1";
+        let book = Book::new();
+        assert!(book.find(lock(&engine(fen))).is_empty());
+        let moves = book.probe(&engine(fen));
+        assert!(moves.contains(&BookMove {
+            iccs: String::from("h9g7"),
+            weight: 761
+        }));
+        assert!(moves.contains(&BookMove {
+            iccs: String::from("h7e7"),
+            weight: 97
+        }));
+    }
+
+    #[test]
+    fn test_out_of_book() {
+        let book = Book::new();
+        assert!(book.probe(&engine("4k4/9/9/9/9/9/9/9/9/4K4 w - - 0 1")).is_empty());
+    }
+}
@@ -1,33 +1,175 @@
 use crate::{
+    book::Book,
     component::{piece::Piece, SelectedPiece},
-    event::{GameoverEvent, SwithPlayerEvent},
+    event::{BroadRefreshEvent, EventAction, GameChangeEvent, GameoverEvent, SwithPlayerEvent},
     game::Data,
-    public::{self, get_piece_render_percent, BroadEntitys},
+    player,
+    public::{self, get_piece_render_percent, BroadEntitys, Pos},
 };
 use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
 use chessai::position;
+use futures_lite::future;
+use rand::{seq::SliceRandom, Rng};
+
+use super::info::PlayerInfoAction;
+
+// 开局库走法落子前的停顿
+pub const BOOK_MOVE_DELAY: f32 = 0.6;
+
+/// AI搜索任务, 在异步线程池中对局面副本进行计算, 结果为ICCS走法
+#[derive(Component)]
+pub struct AiSearch {
+    task: Task<String>,
+    // 开始搜索时的局面, 换边或悔棋后结果作废
+    fen: String,
+}
+
+/// AI开局库走法, 停顿片刻后落子
+#[derive(Component)]
+pub struct AiBookMove {
+    iccs: String,
+    timer: Timer,
+    fen: String,
+}
+
+/// 基于FEN创建引擎副本并按难度开始搜索
+pub fn spawn_search(fen: String, level: player::AiLevel) -> Task<String> {
+    AsyncComputeTaskPool::get().spawn(async move {
+        let mut engine = chessai::Engine::new();
+        engine.from_fen(&fen);
+        // 低难度按概率随机走子
+        if rand::thread_rng().gen_bool(level.blunder_rate()) {
+            if let Some(iccs) = random_move(&mut engine) {
+                info!("随机走子 {iccs}");
+                return iccs;
+            }
+        }
+        let mv = match level {
+            player::AiLevel::Beginner => engine.search_main(2, 100),
+            player::AiLevel::Amateur => engine.search_main(4, 300),
+            player::AiLevel::Expert => engine.search_main(8, 600),
+            player::AiLevel::Master => engine.search_main(64, 1000),
+        };
+        position::move2iccs(mv)
+    })
+}
+
+/// 随机选择一步合法走法
+fn random_move(engine: &mut chessai::Engine) -> Option<String> {
+    let mut moves = Vec::new();
+    for src in 0..90 {
+        for dst in 0..90 {
+            let iccs = position::pos2iccs(src / 9, src % 9, dst / 9, dst % 9);
+            let mv = position::iccs2move(&iccs);
+            // 排除走后被将军的走法
+            if engine.legal_move(mv) && engine.make_move(mv) {
+                engine.undo_make_move();
+                moves.push(iccs);
+            }
+        }
+    }
+    moves.choose(&mut rand::thread_rng()).cloned()
+}
+
+/// 重置对局或返回主菜单时取消AI搜索
+pub fn event_listen(
+    mut events: EventReader<GameChangeEvent>,
+    mut commands: Commands,
+    q_search: Query<Entity, Or<(With<AiSearch>, With<AiBookMove>)>>,
+) {
+    for event in events.iter() {
+        if let EventAction::Despawn = event.0 {
+            for entity in q_search.iter() {
+                info!("cancel ai search");
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
 
 pub fn ai_move(
     mut data: ResMut<Data>,
     mut entitys: ResMut<BroadEntitys>,
     mut commands: Commands,
+    time: Res<Time>,
+    book: Res<Book>,
     mut gameover: EventWriter<GameoverEvent>,
     mut swith_player: EventWriter<SwithPlayerEvent>,
     sound_handles: Res<public::asset::Sounds>,
     image_handles: Res<public::asset::Images>,
     piece_handles: Res<public::asset::Pieces>,
+    mut q_search: Query<(Entity, &mut AiSearch)>,
+    mut q_book: Query<(Entity, &mut AiBookMove)>,
+    mut action_q: Query<(&player::Player, &mut Text), With<PlayerInfoAction>>,
     mut q_piece: Query<(&mut Parent, &mut Piece, &mut Transform, &mut Visibility), With<Piece>>,
+    mut refresh: EventReader<BroadRefreshEvent>,
 ) {
-    if data.current_side.unwrap() != data.ai_side.unwrap() {
+    // 回看时暂停落子, 棋盘刷新的当帧棋子尚未重建, 下一帧再落子
+    if refresh.iter().count() > 0 || data.preview.is_some() {
         return;
     }
-    info!("start ai move {}", data.engine.to_fen());
-    let mv = data.engine.search_main(64, 1000);
+    let ai_side = data.ai_side.unwrap();
+    if data.current_side.unwrap() != ai_side {
+        return;
+    }
+
+    let (iccs, fen) = if let Ok((book_entity, mut book_move)) = q_book.get_single_mut() {
+        // 开局库走法, 等待停顿结束
+        if !book_move.timer.tick(time.delta()).finished() {
+            return;
+        }
+        commands.entity(book_entity).despawn();
+        (book_move.iccs.clone(), book_move.fen.clone())
+    } else if let Ok((search_entity, mut search)) = q_search.get_single_mut() {
+        // 轮询后台搜索结果
+        let iccs = match future::block_on(future::poll_once(&mut search.task)) {
+            Some(iccs) => iccs,
+            None => return,
+        };
+        commands.entity(search_entity).despawn();
+        (iccs, search.fen.clone())
+    } else {
+        // 优先使用开局库, 出库后在后台开始搜索, 后续帧轮询结果
+        let fen = data.engine.to_fen();
+        let action = match book.pick(&data.engine) {
+            Some(iccs) => {
+                info!("开局库走法 {fen} {iccs}");
+                commands.spawn(AiBookMove {
+                    iccs,
+                    timer: Timer::from_seconds(BOOK_MOVE_DELAY, TimerMode::Once),
+                    fen,
+                });
+                "开局库"
+            }
+            None => {
+                info!("start ai move {fen}");
+                commands.spawn(AiSearch {
+                    task: spawn_search(fen.clone(), data.ai_level),
+                    fen,
+                });
+                "思考中"
+            }
+        };
+        for (player, mut text) in action_q.iter_mut() {
+            if player.side == ai_side {
+                text.sections[0].value = String::from(action);
+            }
+        }
+        return;
+    };
+    // 思考期间换边或悔棋, 局面已变, 下一帧重新搜索
+    if fen != data.engine.to_fen() {
+        info!("丢弃过期的AI走法 {fen} {iccs}");
+        return;
+    }
+
+    let mv = position::iccs2move(&iccs);
     let ((src_row, src_col), (dst_row, dst_col)) = position::move2pos(mv);
-    info!("move {mv} {}", position::move2iccs(mv));
+    info!("move {mv} {iccs}");
     let (parent, mut select_piece, mut select_transform, mut select_visiable) =
         q_piece.get_mut(entitys.pieces[src_row][src_col].unwrap()).unwrap();
-    let (src_x, src_y) = get_piece_render_percent(src_row, src_col);
+    let (src_x, src_y) = get_piece_render_percent(src_row, src_col, data.flipped);
     data.selected = data.broad_map[src_row][src_col];
     // 隐藏棋子
     *select_visiable = Visibility::Hidden;
@@ -69,7 +211,7 @@ pub fn ai_move(
         entitys.selected = Some(selected_entity);
     });
     let piece_opt = data.broad_map[dst_row][dst_col];
-    let (dst_x, dst_y) = get_piece_render_percent(dst_row, dst_col);
+    let (dst_x, dst_y) = get_piece_render_percent(dst_row, dst_col, data.flipped);
     info!("棋子{}移动到 row:{} col:{}", data.selected.unwrap().name(), dst_row, dst_col);
 
     if piece_opt.is_some() {
@@ -90,39 +232,18 @@ pub fn ai_move(
     select_piece.col = dst_col;
     select_piece.row = dst_row;
     let piece_entity = entitys.pieces[src_row][src_col];
-    data.broad_map[src_row][src_col] = None;
-    data.broad_map[dst_row][dst_col] = Some(*select_piece);
+    data.make_move(Pos::new(src_row, src_col), Pos::new(dst_row, dst_col));
     entitys.pieces[src_row][src_col] = None;
     entitys.pieces[dst_row][dst_col] = piece_entity;
 
     // 取消选棋子动画
     commands.entity(entitys.selected.unwrap()).despawn_recursive();
 
-    // 设置引擎移动
-    data.engine.make_move(mv);
-
-    // 检测是否胜利
-    if let Some(winner) = data.engine.winner() {
-        gameover.send(GameoverEvent(winner));
-        return;
-    }
-
-    // 检测是否将军
-    if data.engine.in_check() {
-        // 将军
-        info!("将军");
-        commands.spawn(super::audio::play_once(sound_handles.check.clone()));
-    } else {
-        // 是否吃子
-        if data.engine.captured() {
-            // 吃子
-            commands.spawn(super::audio::play_once(sound_handles.eat.clone()));
-        } else {
-            // 移动
-            commands.spawn(super::audio::play_once(sound_handles.go.clone()));
-        }
-    }
-    // 切换棋手
-    info!("send swith event");
-    swith_player.send(SwithPlayerEvent);
+    super::chess::finish_move(
+        &mut data,
+        &mut commands,
+        &sound_handles,
+        &mut gameover,
+        &mut swith_player,
+    );
 }
@@ -1,6 +1,5 @@
 use bevy::prelude::{default, AudioSource, AudioSourceBundle, Handle, PlaybackSettings};
 
-
 pub fn play_once(sound: Handle<AudioSource>) -> AudioSourceBundle {
     AudioSourceBundle {
         source: sound,
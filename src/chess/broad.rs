@@ -1,6 +1,8 @@
-use crate::chess::previou::{PiecePreviouEnd, PiecePreviouStart, PiecePreviouTimer};
-use crate::component;
-use crate::event::{EventAction, GameChangeEvent};
+use crate::chess::previou::{
+    PiecePreviouEnd, PiecePreviouMove, PiecePreviouStart, PiecePreviouTimer,
+};
+use crate::component::{self, piece::Piece, SelectedPiece};
+use crate::event::{BroadRefreshEvent, EventAction, GameChangeEvent};
 use crate::public::BroadEntitys;
 use crate::{game::Data, public};
 use bevy::prelude::*;
@@ -8,7 +10,7 @@ use bevy::prelude::*;
 pub fn event_listen(
     mut events: EventReader<GameChangeEvent>,
     mut commands: Commands,
-    mut data: ResMut<Data>,
+    data: Res<Data>,
     mut entitys: ResMut<BroadEntitys>,
     image_handles: Res<public::asset::Images>,
     animate_handles: Res<public::asset::Animates>,
@@ -62,29 +64,13 @@ pub fn event_listen(
                         ));
 
                         // 渲染棋子
-                        for (row, pieces) in data.broad_map.iter_mut().enumerate() {
-                            for (col, piece) in pieces.iter_mut().enumerate() {
-                                if let Some(piece) = piece {
-                                    let (x, y) = public::get_piece_render_percent(row, col);
-                                    info!("渲染棋子: {} x:{}, y:{}", piece.name(), x, y);
-                                    let entity = parent
-                                        .spawn((
-                                            SpriteBundle {
-                                                texture: piece_handles.get_handle(&piece, false),
-                                                transform: Transform::from_xyz(x, y, 1_f32),
-                                                sprite: Sprite {
-                                                    custom_size: Some(Vec2::new(76_f32, 76_f32)),
-                                                    ..default()
-                                                },
-                                                ..default()
-                                            },
-                                            *piece,
-                                        ))
-                                        .id();
-                                    entitys.pieces[row][col] = Some(entity);
-                                }
-                            }
-                        }
+                        spawn_pieces(
+                            parent,
+                            &data.broad_map,
+                            data.flipped,
+                            &mut entitys,
+                            &piece_handles,
+                        );
                     })
                     .id();
                 entitys.broad = Some(broad_entity);
@@ -96,6 +82,13 @@ pub fn event_listen(
             EventAction::Despawn => {
                 let (entity, _) = broad_query.single_mut();
                 commands.entity(entity).despawn_recursive();
+                // 清除结束标记
+                if let Some(gameover) = entitys.gameover.take() {
+                    commands.entity(gameover).despawn_recursive();
+                }
+                entitys.broad = None;
+                entitys.selected = None;
+                entitys.pieces = Default::default();
             }
             EventAction::Visibie => {
                 let (_, mut broad_visible) = broad_query.single_mut();
@@ -104,3 +97,82 @@ pub fn event_listen(
         }
     }
 }
+
+/// 根据棋盘地图重新渲染全部棋子与最近一步标记, 回看时渲染回看的局面
+pub fn refresh_listen(
+    mut events: EventReader<BroadRefreshEvent>,
+    mut commands: Commands,
+    data: Res<Data>,
+    mut entitys: ResMut<BroadEntitys>,
+    piece_handles: Res<public::asset::Pieces>,
+    piece_query: Query<Entity, Or<(With<Piece>, With<SelectedPiece>)>>,
+    mut previou_query: Query<&mut Visibility, Or<(With<PiecePreviouStart>, With<PiecePreviouEnd>)>>,
+) {
+    for _ in events.iter() {
+        let broad_entity = match entitys.broad {
+            Some(entity) => entity,
+            None => return,
+        };
+        info!("刷新棋盘");
+        for entity in piece_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        entitys.selected = None;
+        entitys.pieces = Default::default();
+        let (broad_map, last_move) = match data.preview {
+            Some(serial) if serial <= data.history.len() => {
+                let last_move = serial.checked_sub(1).map(|i| &data.history[i]);
+                (data.position_at(serial), last_move.map(|step| (step.src, step.dst)))
+            }
+            _ => (data.broad_map, data.get_last_move()),
+        };
+        commands.entity(broad_entity).with_children(|parent| {
+            spawn_pieces(parent, &broad_map, data.flipped, &mut entitys, &piece_handles);
+        });
+
+        // 最近一步标记
+        match last_move {
+            Some((src, dst)) => {
+                commands.spawn(PiecePreviouMove(src, dst));
+            }
+            None => {
+                for mut visibility in previou_query.iter_mut() {
+                    *visibility = Visibility::Hidden;
+                }
+            }
+        }
+        return;
+    }
+}
+
+fn spawn_pieces(
+    parent: &mut ChildBuilder,
+    broad_map: &[[Option<Piece>; 9]; 10],
+    flipped: bool,
+    entitys: &mut BroadEntitys,
+    piece_handles: &public::asset::Pieces,
+) {
+    for (row, pieces) in broad_map.iter().enumerate() {
+        for (col, piece) in pieces.iter().enumerate() {
+            if let Some(piece) = piece {
+                let (x, y) = public::get_piece_render_percent(row, col, flipped);
+                info!("渲染棋子: {} x:{}, y:{}", piece.name(), x, y);
+                let entity = parent
+                    .spawn((
+                        SpriteBundle {
+                            texture: piece_handles.get_handle(piece, false),
+                            transform: Transform::from_xyz(x, y, 1_f32),
+                            sprite: Sprite {
+                                custom_size: Some(Vec2::new(76_f32, 76_f32)),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        *piece,
+                    ))
+                    .id();
+                entitys.pieces[row][col] = Some(entity);
+            }
+        }
+    }
+}
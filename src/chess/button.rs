@@ -1,9 +1,16 @@
 use crate::component::ChessButtonGroup;
-use crate::event::{EventAction, GameChangeEvent};
-use crate::game::Data;
-use crate::public;
+use crate::event::{
+    BroadRefreshEvent, DeduceAction, DeduceEvent, EventAction, GameChangeEvent, GameoverEvent,
+    PeaceEvent, PositionEvent, PromptEvent, RetractEvent,
+};
+use crate::game::{Data, GameMode};
+use crate::net::{Message, NetConnection};
+use crate::status::ChessState;
+use crate::{pgn, player, public};
 use bevy::prelude::*;
 
+use super::info::PlayerInfoTitle;
+
 /// GameButton 对局按钮, 位于棋盘正下方
 #[derive(Component)]
 pub enum ChessButton {
@@ -21,6 +28,16 @@ pub enum ChessButton {
     Swap,
     // 翻转棋盘
     Roll,
+    // 打谱后退
+    Back,
+    // 打谱前进
+    Forward,
+    // 打谱分析
+    Analysis,
+    // 保存棋谱
+    Save,
+    // 载入或复制局面
+    Position,
 }
 
 pub const GAME_MENU_NEW_GAME_TEXT: &str = "新局";
@@ -30,6 +47,11 @@ pub const GAME_MENU_PROMPT_TEXT: &str = "提示";
 pub const GAME_MENU_ADMIT_DEFEAT_TEXT: &str = "认输";
 pub const GAME_MENU_SWAP_TEXT: &str = "换边";
 pub const GAME_MENU_ROLL_TEXT: &str = "翻转";
+pub const GAME_MENU_BACK_TEXT: &str = "后退";
+pub const GAME_MENU_FORWARD_TEXT: &str = "前进";
+pub const GAME_MENU_ANALYSIS_TEXT: &str = "分析";
+pub const GAME_MENU_SAVE_TEXT: &str = "保存";
+pub const GAME_MENU_POSITION_TEXT: &str = "局面";
 
 // 游戏菜单配置
 pub const GAME_MENU_NORMAL_BUTTON_COLOR: Color = Color::NONE;
@@ -39,6 +61,7 @@ pub const GAME_MENU_PRESSED_BUTTON_COLOR: Color = Color::rgb(0.45, 0.45, 0.45);
 pub fn event_listen(
     mut events: EventReader<GameChangeEvent>,
     mut commands: Commands,
+    data: Res<Data>,
     fonts: Res<public::asset::Fonts>,
     mut botton_q: Query<(Entity, &mut Visibility), With<ChessButtonGroup>>,
 ) {
@@ -68,7 +91,7 @@ pub fn event_listen(
                         ChessButtonGroup,
                     ))
                     .with_children(|parent| {
-                        make_chess_buttons(parent, fonts.xiaoli.clone());
+                        make_chess_buttons(parent, fonts.xiaoli.clone(), data.mode);
                     });
             }
             EventAction::Hidden => {
@@ -92,7 +115,38 @@ pub fn event_listen(
     }
 }
 
-pub fn make_chess_buttons(parent: &mut ChildBuilder, font: Handle<Font>) {
+pub fn make_chess_buttons(parent: &mut ChildBuilder, font: Handle<Font>, mode: Option<GameMode>) {
+    if mode == Some(GameMode::DeduceGame) {
+        // 打谱没有胜负, 只需前进后退与分析
+        make_text_bundle(parent, font.clone(), GAME_MENU_NEW_GAME_TEXT, ChessButton::NewGame);
+        make_text_bundle(parent, font.clone(), GAME_MENU_BACK_TEXT, ChessButton::Back);
+        make_text_bundle(parent, font.clone(), GAME_MENU_FORWARD_TEXT, ChessButton::Forward);
+        make_text_bundle(parent, font.clone(), GAME_MENU_PROMPT_TEXT, ChessButton::Prompt);
+        make_text_bundle(parent, font.clone(), GAME_MENU_ANALYSIS_TEXT, ChessButton::Analysis);
+        make_text_bundle(parent, font.clone(), GAME_MENU_ROLL_TEXT, ChessButton::Roll);
+        make_text_bundle(parent, font.clone(), GAME_MENU_SAVE_TEXT, ChessButton::Save);
+        make_text_bundle(parent, font.clone(), GAME_MENU_POSITION_TEXT, ChessButton::Position);
+        return;
+    }
+    if mode == Some(GameMode::WatchGame) {
+        // 观战只能翻转棋盘与保存棋谱
+        make_text_bundle(parent, font.clone(), GAME_MENU_ROLL_TEXT, ChessButton::Roll);
+        make_text_bundle(parent, font.clone(), GAME_MENU_SAVE_TEXT, ChessButton::Save);
+        return;
+    }
+    if mode == Some(GameMode::InterGame) {
+        // 联机对弈不能悔棋和换边
+        make_text_bundle(parent, font.clone(), GAME_MENU_PEACE_TEXT, ChessButton::Peact);
+        make_text_bundle(
+            parent,
+            font.clone(),
+            GAME_MENU_ADMIT_DEFEAT_TEXT,
+            ChessButton::AdmitDefeat,
+        );
+        make_text_bundle(parent, font.clone(), GAME_MENU_ROLL_TEXT, ChessButton::Roll);
+        make_text_bundle(parent, font.clone(), GAME_MENU_SAVE_TEXT, ChessButton::Save);
+        return;
+    }
     make_text_bundle(parent, font.clone(), GAME_MENU_NEW_GAME_TEXT, ChessButton::NewGame);
     make_text_bundle(parent, font.clone(), GAME_MENU_RETRACT_TEXT, ChessButton::Retract);
     make_text_bundle(parent, font.clone(), GAME_MENU_PEACE_TEXT, ChessButton::Peact);
@@ -100,6 +154,8 @@ pub fn make_chess_buttons(parent: &mut ChildBuilder, font: Handle<Font>) {
     make_text_bundle(parent, font.clone(), GAME_MENU_ADMIT_DEFEAT_TEXT, ChessButton::AdmitDefeat);
     make_text_bundle(parent, font.clone(), GAME_MENU_SWAP_TEXT, ChessButton::Swap);
     make_text_bundle(parent, font.clone(), GAME_MENU_ROLL_TEXT, ChessButton::Roll);
+    make_text_bundle(parent, font.clone(), GAME_MENU_SAVE_TEXT, ChessButton::Save);
+    make_text_bundle(parent, font.clone(), GAME_MENU_POSITION_TEXT, ChessButton::Position);
 }
 
 pub fn make_text_bundle(
@@ -152,6 +208,18 @@ pub fn make_text_bundle(
 
 pub fn chess_button_system(
     mut data: ResMut<Data>,
+    mut retract: EventWriter<RetractEvent>,
+    mut prompt: EventWriter<PromptEvent>,
+    mut peace: EventWriter<PeaceEvent>,
+    mut position: EventWriter<PositionEvent>,
+    mut gameover: EventWriter<GameoverEvent>,
+    mut game_change: EventWriter<GameChangeEvent>,
+    mut deduce: EventWriter<DeduceEvent>,
+    mut refresh: EventWriter<BroadRefreshEvent>,
+    mut title_q: Query<(&mut player::Player, &mut Text), With<PlayerInfoTitle>>,
+    connection: Option<Res<NetConnection>>,
+    state: Res<State<ChessState>>,
+    mut chess_state: ResMut<NextState<ChessState>>,
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor, &ChessButton),
         (Changed<Interaction>, With<Button>),
@@ -161,27 +229,86 @@ pub fn chess_button_system(
         match *interaction {
             Interaction::Pressed => {
                 *color = GAME_MENU_PRESSED_BUTTON_COLOR.into();
+                // 对局结束后只能开始新局、保存棋谱或载入局面
+                if *state.get() == ChessState::Gameover
+                    && !matches!(
+                        menu,
+                        ChessButton::NewGame | ChessButton::Save | ChessButton::Position
+                    )
+                {
+                    continue;
+                }
                 match menu {
                     ChessButton::NewGame => {
-                        info!("todo GameMenu NewGame");
+                        info!("GameMenu NewGame");
+                        data.reset();
+                        game_change.send(GameChangeEvent(EventAction::Despawn));
+                        game_change.send(GameChangeEvent(EventAction::Spawn));
+                        chess_state.set(data.chess_state());
                     }
                     ChessButton::Retract => {
-                        info!("todo GameMenu Retract");
+                        info!("GameMenu Retract");
+                        retract.send(RetractEvent);
                     }
                     ChessButton::Peact => {
-                        info!("todo GameMenu Peact");
+                        info!("GameMenu Peact");
+                        peace.send(PeaceEvent);
                     }
                     ChessButton::Prompt => {
-                        info!("todo GameMenu Prompt");
+                        info!("GameMenu Prompt");
+                        prompt.send(PromptEvent);
                     }
                     ChessButton::AdmitDefeat => {
-                        info!("todo GameMenu AdmitDefeat");
+                        // 本机一方认输, 对方获胜; 联机时对方行棋也可以认输
+                        let side = match data.resign_side() {
+                            Some(side) => side,
+                            None => continue,
+                        };
+                        info!("GameMenu AdmitDefeat {}方认输", side.name());
+                        gameover.send(GameoverEvent(side.opponent().winner()));
+                        if let (Some(GameMode::InterGame), Some(connection)) =
+                            (data.mode, &connection)
+                        {
+                            connection.send(Message::Resign);
+                        }
                     }
                     ChessButton::Swap => {
-                        info!("todo GameMenu Swap");
+                        if data.mode != Some(GameMode::AiGame) {
+                            info!("GameMenu Swap 仅支持人机对弈");
+                            continue;
+                        }
+                        // 交换AI执棋方, 轮到AI时立即开始行棋
+                        let ai_side = data.ai_side.unwrap().opponent();
+                        info!("GameMenu Swap AI执{}", ai_side.name());
+                        data.set_ai_side(ai_side);
+                        super::info::update_title_text(&data, &mut title_q);
+                        refresh.send(BroadRefreshEvent);
+                        chess_state.set(data.chess_state());
                     }
                     ChessButton::Roll => {
-                        info!("todo GameMenu Roll");
+                        data.flipped = !data.flipped;
+                        info!("GameMenu Roll flipped:{}", data.flipped);
+                        refresh.send(BroadRefreshEvent);
+                    }
+                    ChessButton::Back => {
+                        info!("GameMenu Back");
+                        deduce.send(DeduceEvent(DeduceAction::Back));
+                    }
+                    ChessButton::Forward => {
+                        info!("GameMenu Forward");
+                        deduce.send(DeduceEvent(DeduceAction::Forward));
+                    }
+                    ChessButton::Analysis => {
+                        info!("GameMenu Analysis");
+                        deduce.send(DeduceEvent(DeduceAction::Analysis));
+                    }
+                    ChessButton::Save => match pgn::save(&mut data) {
+                        Ok(path) => info!("GameMenu Save 棋谱已保存到 {}", path.display()),
+                        Err(err) => warn!("GameMenu Save 保存棋谱失败: {err}"),
+                    },
+                    ChessButton::Position => {
+                        info!("GameMenu Position");
+                        position.send(PositionEvent);
                     }
                 }
             }
@@ -0,0 +1,467 @@
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+
+use crate::{
+    event::{EventAction, GameChangeEvent},
+    game::{Data, GameMode},
+    net::{protocol, Message, NetConnection},
+    public,
+};
+
+use super::info::PlayerInfoPanel;
+
+// 快捷短语
+pub const CHAT_PHRASES: [&str; 4] = ["你好", "好棋", "快点", "再来一局"];
+pub const CHAT_MUTE_TEXT: &str = "屏蔽对方";
+pub const CHAT_UNMUTE_TEXT: &str = "取消屏蔽";
+pub const CHAT_INPUT_HINT: &str = "输入后回车发送";
+// 显示的行数, 更早的消息滚轮翻看
+pub const CHAT_VISIBLE_LINES: usize = 6;
+// 保留的消息条数
+pub const CHAT_HISTORY_MAX: usize = 100;
+
+pub const CHAT_NORMAL_BUTTON_COLOR: Color = Color::rgba(0.2, 0.2, 0.2, 0.6);
+pub const CHAT_HOVERED_BUTTON_COLOR: Color = Color::rgb(0.30, 0.30, 0.30);
+pub const CHAT_PRESSED_BUTTON_COLOR: Color = Color::rgb(0.45, 0.45, 0.45);
+
+/// 聊天记录区域, 鼠标悬停时滚轮翻看
+#[derive(Component)]
+pub struct ChatLog;
+
+/// 聊天记录文本
+#[derive(Component)]
+pub struct ChatLogText;
+
+/// 正在输入的内容
+#[derive(Component)]
+pub struct ChatInputText;
+
+/// 屏蔽按钮文本
+#[derive(Component)]
+pub struct ChatMuteText;
+
+#[derive(Component)]
+pub enum ChatButton {
+    // 快捷短语序号
+    Phrase(usize),
+    // 屏蔽/取消屏蔽对方
+    Mute,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatSender {
+    Home,
+    Away,
+    // 本地提示
+    System,
+}
+
+impl ChatSender {
+    pub fn name(&self) -> &str {
+        match self {
+            ChatSender::Home => "我",
+            ChatSender::Away => "对方",
+            ChatSender::System => "提示",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            ChatSender::Home => Color::ANTIQUE_WHITE,
+            ChatSender::Away => Color::GOLD,
+            ChatSender::System => Color::GRAY,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatLine {
+    pub sender: ChatSender,
+    pub text: String,
+}
+
+/// 联机对弈的聊天记录
+#[derive(Resource, Debug, Default)]
+pub struct ChatHistory {
+    lines: Vec<ChatLine>,
+    // 向上翻看的行数, 0为最新
+    scroll: usize,
+    // 屏蔽对方消息
+    pub muted: bool,
+    // 正在输入的内容
+    pub input: String,
+}
+
+impl ChatHistory {
+    /// 记录一条消息, 屏蔽时丢弃对方消息并返回false
+    pub fn push(&mut self, sender: ChatSender, text: String) -> bool {
+        if self.muted && sender == ChatSender::Away {
+            return false;
+        }
+        self.lines.push(ChatLine { sender, text });
+        if self.lines.len() > CHAT_HISTORY_MAX {
+            self.lines.remove(0);
+        }
+        // 翻看历史时保持当前位置
+        if self.scroll > 0 {
+            self.scroll(1);
+        }
+        true
+    }
+
+    /// 滚动记录, 正数向上翻看
+    pub fn scroll(&mut self, delta: isize) {
+        let max = self.lines.len().saturating_sub(CHAT_VISIBLE_LINES) as isize;
+        self.scroll = (self.scroll as isize + delta).clamp(0, max) as usize;
+    }
+
+    /// 当前显示的消息
+    pub fn visible(&self) -> &[ChatLine] {
+        let end = self.lines.len() - self.scroll;
+        &self.lines[end.saturating_sub(CHAT_VISIBLE_LINES)..end]
+    }
+}
+
+/// 发送一条聊天消息并记录, 对局结束后对方离开时只提示
+fn send(connection: Option<&NetConnection>, history: &mut ChatHistory, text: &str) {
+    let text = match protocol::chat_text(text) {
+        Some(text) => text,
+        None => return,
+    };
+    match connection {
+        Some(connection) => {
+            connection.send(Message::Chat(text.clone()));
+            history.push(ChatSender::Home, text);
+        }
+        None => {
+            history.push(ChatSender::System, String::from("对方已离开"));
+        }
+    }
+}
+
+/// 聊天面板, 位于本方信息框下方, 随棋盘翻转左右交换
+pub fn spawn_panel(parent: &mut ChildBuilder, on_left: bool, fonts: &public::asset::Fonts) {
+    let mut style = Style {
+        position_type: PositionType::Absolute,
+        top: Val::Percent(58_f32),
+        width: Val::Px(200_f32),
+        height: Val::Px(250_f32),
+        flex_direction: FlexDirection::Column,
+        padding: UiRect::all(Val::Px(4_f32)),
+        ..default()
+    };
+    if on_left {
+        style.left = Val::Percent(10_f32);
+    } else {
+        style.right = Val::Percent(10_f32);
+    }
+    let text_style = TextStyle {
+        font: fonts.wenkai.clone(),
+        font_size: 18_f32,
+        color: Color::ANTIQUE_WHITE,
+    };
+
+    parent
+        .spawn((
+            NodeBundle {
+                style,
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.35).into(),
+                ..default()
+            },
+            PlayerInfoPanel,
+        ))
+        .with_children(|parent| {
+            // 聊天记录
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Percent(100_f32),
+                            height: Val::Px(132_f32),
+                            flex_direction: FlexDirection::Column,
+                            justify_content: JustifyContent::FlexEnd,
+                            overflow: Overflow::clip(),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    Interaction::default(),
+                    ChatLog,
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section("", text_style.clone()).with_style(Style {
+                            max_width: Val::Px(192_f32),
+                            ..default()
+                        }),
+                        ChatLogText,
+                    ));
+                });
+
+            // 输入框
+            parent.spawn((
+                TextBundle::from_section(
+                    CHAT_INPUT_HINT,
+                    TextStyle {
+                        color: Color::GRAY,
+                        ..text_style.clone()
+                    },
+                )
+                .with_style(Style {
+                    height: Val::Px(24_f32),
+                    margin: UiRect::vertical(Val::Px(4_f32)),
+                    overflow: Overflow::clip(),
+                    ..default()
+                }),
+                ChatInputText,
+            ));
+
+            // 快捷短语与屏蔽
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_wrap: FlexWrap::Wrap,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for (i, phrase) in CHAT_PHRASES.iter().enumerate() {
+                        make_chat_button(parent, fonts, phrase, ChatButton::Phrase(i), false);
+                    }
+                    make_chat_button(parent, fonts, CHAT_MUTE_TEXT, ChatButton::Mute, true);
+                });
+        });
+}
+
+fn make_chat_button(
+    parent: &mut ChildBuilder,
+    fonts: &public::asset::Fonts,
+    text: &str,
+    button: ChatButton,
+    mute: bool,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::axes(Val::Px(6_f32), Val::Px(2_f32)),
+                    margin: UiRect::all(Val::Px(2_f32)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: CHAT_NORMAL_BUTTON_COLOR.into(),
+                ..default()
+            },
+            button,
+        ))
+        .with_children(|parent| {
+            let text = TextBundle::from_section(
+                text,
+                TextStyle {
+                    font: fonts.xiaoli.clone(),
+                    font_size: 18_f32,
+                    color: Color::ANTIQUE_WHITE,
+                },
+            );
+            if mute {
+                parent.spawn((text, ChatMuteText));
+            } else {
+                parent.spawn(text);
+            }
+        });
+}
+
+/// 快捷短语与屏蔽按钮
+pub fn chat_button_system(
+    connection: Option<Res<NetConnection>>,
+    mut history: ResMut<ChatHistory>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &ChatButton),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut color, button) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = CHAT_PRESSED_BUTTON_COLOR.into();
+                match button {
+                    ChatButton::Phrase(i) => {
+                        send(connection.as_deref(), &mut history, CHAT_PHRASES[*i]);
+                    }
+                    ChatButton::Mute => {
+                        history.muted = !history.muted;
+                        info!("屏蔽对方消息: {}", history.muted);
+                        let text = match history.muted {
+                            true => "已屏蔽对方消息",
+                            false => "已取消屏蔽",
+                        };
+                        history.push(ChatSender::System, String::from(text));
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *color = CHAT_HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *color = CHAT_NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+/// 键盘输入聊天内容, 回车发送
+pub fn input_system(
+    keys: Res<Input<KeyCode>>,
+    mut chars: EventReader<ReceivedCharacter>,
+    connection: Option<Res<NetConnection>>,
+    mut history: ResMut<ChatHistory>,
+) {
+    if keys.just_pressed(KeyCode::Back) && !history.input.is_empty() {
+        history.input.pop();
+    }
+    for event in chars.iter() {
+        if !event.char.is_control() && history.input.chars().count() < protocol::CHAT_MAX_LEN {
+            history.input.push(event.char);
+        }
+    }
+    if keys.just_pressed(KeyCode::Return) && !history.input.is_empty() {
+        let input = std::mem::take(&mut history.input);
+        send(connection.as_deref(), &mut history, &input);
+    }
+}
+
+/// 鼠标悬停在聊天记录上时滚轮翻看
+pub fn scroll_system(
+    mut wheel: EventReader<MouseWheel>,
+    mut history: ResMut<ChatHistory>,
+    log_q: Query<&Interaction, With<ChatLog>>,
+) {
+    let hovered = log_q.iter().any(|interaction| *interaction != Interaction::None);
+    for event in wheel.iter() {
+        if hovered && event.y != 0_f32 {
+            history.scroll(event.y.signum() as isize);
+        }
+    }
+}
+
+/// 聊天记录变化时刷新面板
+pub fn render_system(
+    history: Res<ChatHistory>,
+    mut log_q: Query<&mut Text, (With<ChatLogText>, Without<ChatInputText>)>,
+    mut input_q: Query<&mut Text, (With<ChatInputText>, Without<ChatMuteText>)>,
+    mut mute_q: Query<&mut Text, (With<ChatMuteText>, Without<ChatLogText>)>,
+) {
+    if !history.is_changed() {
+        return;
+    }
+    for mut text in log_q.iter_mut() {
+        let lines = history.visible();
+        let style = text.sections[0].style.clone();
+        text.sections = lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let end = if i + 1 < lines.len() { "\n" } else { "" };
+                TextSection::new(
+                    format!("{}: {}{}", line.sender.name(), line.text, end),
+                    TextStyle {
+                        color: line.sender.color(),
+                        ..style.clone()
+                    },
+                )
+            })
+            .collect();
+        if text.sections.is_empty() {
+            text.sections.push(TextSection::new("", style));
+        }
+    }
+    for mut text in input_q.iter_mut() {
+        let section = &mut text.sections[0];
+        if history.input.is_empty() {
+            section.value = String::from(CHAT_INPUT_HINT);
+            section.style.color = Color::GRAY;
+        } else {
+            section.value = format!("{}_", history.input);
+            section.style.color = Color::ANTIQUE_WHITE;
+        }
+    }
+    for mut text in mute_q.iter_mut() {
+        text.sections[0].value = match history.muted {
+            true => String::from(CHAT_UNMUTE_TEXT),
+            false => String::from(CHAT_MUTE_TEXT),
+        };
+    }
+}
+
+/// 联机对弈开局时创建聊天记录, 返回主菜单时清除; 面板随本方信息框创建与销毁
+pub fn event_listen(
+    mut commands: Commands,
+    mut events: EventReader<GameChangeEvent>,
+    data: Res<Data>,
+) {
+    for event in events.iter() {
+        match event.0 {
+            EventAction::Spawn if data.mode == Some(GameMode::InterGame) => {
+                commands.init_resource::<ChatHistory>();
+            }
+            EventAction::Despawn => {
+                commands.remove_resource::<ChatHistory>();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(count: usize) -> ChatHistory {
+        let mut history = ChatHistory::default();
+        for i in 0..count {
+            history.push(ChatSender::Home, i.to_string());
+        }
+        history
+    }
+
+    #[test]
+    fn test_push_muted() {
+        let mut history = ChatHistory::default();
+        assert!(history.push(ChatSender::Away, String::from("好棋")));
+        history.muted = true;
+        assert!(!history.push(ChatSender::Away, String::from("快点")));
+        assert!(history.push(ChatSender::Home, String::from("再来一局")));
+        let texts: Vec<&str> = history.visible().iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(texts, vec!["好棋", "再来一局"]);
+    }
+
+    #[test]
+    fn test_scroll() {
+        let mut history = filled(10);
+        assert_eq!(history.visible().len(), CHAT_VISIBLE_LINES);
+        assert_eq!(history.visible().last().unwrap().text, "9");
+        history.scroll(2);
+        assert_eq!(history.visible().last().unwrap().text, "7");
+        // 翻看时收到新消息, 显示位置不变
+        history.push(ChatSender::Away, String::from("10"));
+        assert_eq!(history.visible().last().unwrap().text, "7");
+        history.scroll(100);
+        assert_eq!(history.visible()[0].text, "0");
+        history.scroll(-100);
+        assert_eq!(history.visible().last().unwrap().text, "10");
+
+        // 不足一屏时不能滚动
+        let mut history = filled(2);
+        history.scroll(1);
+        assert_eq!(history.visible().len(), 2);
+    }
+
+    #[test]
+    fn test_history_max() {
+        let history = filled(CHAT_HISTORY_MAX + 5);
+        assert_eq!(history.lines.len(), CHAT_HISTORY_MAX);
+        assert_eq!(history.lines[0].text, "5");
+    }
+}
@@ -1,11 +1,11 @@
 use bevy::{prelude::*, window::PrimaryWindow};
-use chessai::position::{iccs2move, pos2iccs};
 
 use crate::{
     component::{piece::Piece, ChineseBroadCamera, SelectedPiece},
     event::{GameoverEvent, SwithPlayerEvent},
-    game::Data,
-    public::{self, get_piece_render_percent, BroadEntitys},
+    game::{Data, GameMode},
+    net::NetConnection,
+    public::{self, get_piece_pos, get_piece_render_percent, BroadEntitys, Pos},
 };
 
 pub fn selection(
@@ -22,7 +22,13 @@ pub fn selection(
     q_camera: Query<(&Camera, &GlobalTransform), With<ChineseBroadCamera>>,
     mut q_select: Query<&mut Transform, (With<SelectedPiece>, Without<Piece>)>,
     mut q_piece: Query<(&mut Parent, &mut Piece, &mut Transform, &mut Visibility), With<Piece>>,
+    connection: Option<Res<NetConnection>>,
+    session: Option<ResMut<super::inter::InterSession>>,
 ) {
+    // 回看时只显示不能行棋
+    if data.preview.is_some() {
+        return;
+    }
     let (camera, camera_transform) = q_camera.single();
     let window = q_window.single();
 
@@ -32,24 +38,9 @@ pub fn selection(
         .map(|ray| ray.origin.truncate())
     {
         if buttons.just_pressed(MouseButton::Left) {
-            let (min_x, min_y) = get_piece_render_percent(0, 0);
-            let (max_x, max_y) = get_piece_render_percent(10, 9);
-
-            // 判断是否在棋盘内
-            if pos.x >= min_x - 27_f32
-                && pos.y >= min_y - 27_f32
-                && pos.x <= max_x + 27_f32
-                && pos.y <= max_y + 27_f32
-            {
-                // 计算棋盘坐标
-                let col = ((pos.x + 274_f32) / 68_f32).round() as usize;
-                let row = ((pos.y + 285_f32) / 68_f32).round() as usize;
-                let (x, y) = get_piece_render_percent(row, col);
-
-                // 计算选择点是否超出棋子边缘: 选择点到棋心的直线距离是否大于30
-                if ((x - pos.x).abs().powi(2) + (y - pos.y).abs().powi(2)).sqrt() > 30_f32 {
-                    return;
-                }
+            // 计算棋盘坐标
+            if let Some((row, col)) = get_piece_pos(pos.x, pos.y, data.flipped) {
+                let (x, y) = get_piece_render_percent(row, col, data.flipped);
 
                 let piece_opt = data.broad_map[row][col];
 
@@ -108,10 +99,9 @@ pub fn selection(
                 // 判断行子或吃子是否合法
                 let select_piece: Piece = data.selected.unwrap();
 
-                let iccs = pos2iccs(select_piece.row, select_piece.col, row, col);
-                let user_mv = iccs2move(&iccs);
-                // 非法行棋
-                if !data.engine.legal_move(user_mv) {
+                // 非法行棋, 包括走后被将军
+                if !data.can_move(Pos::new(select_piece.row, select_piece.col), Pos::new(row, col))
+                {
                     let (_, _, _, mut visibile) = q_piece
                         .get_mut(entitys.pieces[select_piece.row][select_piece.col].unwrap())
                         .unwrap();
@@ -152,8 +142,7 @@ pub fn selection(
                 // 改变游戏数据
                 piece.col = col;
                 piece.row = row;
-                data.broad_map[select_piece.row][select_piece.col] = None;
-                data.broad_map[row][col] = Some(*piece);
+                data.make_move(Pos::new(select_piece.row, select_piece.col), Pos::new(row, col));
                 entitys.pieces[select_piece.row][select_piece.col] = None;
                 entitys.pieces[row][col] = Some(piece_entity);
 
@@ -163,33 +152,57 @@ pub fn selection(
                 // 显示棋子
                 *visibile = Visibility::Inherited;
 
-                data.engine.make_move(user_mv);
-
-                // 检测是否胜利
-                if let Some(winner) = data.engine.winner() {
-                    gameover.send(GameoverEvent(winner));
-                    return;
-                }
-                // 检测是否将军
-                if data.engine.in_check() {
-                    // 将军
-                    info!("将军");
-                    commands.spawn(super::audio::play_once(sound_handles.check.clone()));
-                } else {
-                    // 是否吃子
-                    if data.engine.captured() {
-                        // 吃子
-                        commands.spawn(super::audio::play_once(sound_handles.eat.clone()));
-                    } else {
-                        // 移动
-                        commands.spawn(super::audio::play_once(sound_handles.go.clone()));
-                    }
+                // 联机对弈时把走法发给对方
+                if let (Some(GameMode::InterGame), Some(connection), Some(mut session)) =
+                    (data.mode, &connection, session)
+                {
+                    super::inter::send_move(connection, &mut session, &data);
                 }
-
-                // 切换棋手
-                info!("send swith event");
-                swith_player.send(SwithPlayerEvent);
+                finish_move(
+                    &mut data,
+                    &mut commands,
+                    &sound_handles,
+                    &mut gameover,
+                    &mut swith_player,
+                );
             }
         }
     }
 }
+
+/// 落子后判定胜负、播放音效并切换棋手, 本地、AI与联机走法共用
+pub fn finish_move(
+    data: &mut Data,
+    commands: &mut Commands,
+    sound_handles: &public::asset::Sounds,
+    gameover: &mut EventWriter<GameoverEvent>,
+    swith_player: &mut EventWriter<SwithPlayerEvent>,
+) {
+    // 检测是否胜利, 打谱不判胜负
+    if let Some(winner) = data.engine.winner() {
+        if data.mode != Some(GameMode::DeduceGame) {
+            gameover.send(GameoverEvent(winner));
+            return;
+        }
+        info!("打谱 对局已分胜负");
+    }
+    // 检测是否将军
+    if data.engine.in_check() {
+        // 将军
+        info!("将军");
+        commands.spawn(super::audio::play_once(sound_handles.check.clone()));
+    } else {
+        // 是否吃子
+        if data.engine.captured() {
+            // 吃子
+            commands.spawn(super::audio::play_once(sound_handles.eat.clone()));
+        } else {
+            // 移动
+            commands.spawn(super::audio::play_once(sound_handles.go.clone()));
+        }
+    }
+
+    // 切换棋手
+    info!("send swith event");
+    swith_player.send(SwithPlayerEvent);
+}
@@ -0,0 +1,70 @@
+use bevy::prelude::*;
+
+use crate::{
+    component::piece::Side,
+    event::GameoverEvent,
+    game::{Data, GameMode},
+    player::{self, TimerState},
+    public,
+};
+
+use super::{
+    info::{PlayerInfoCurrentTimer, PlayerInfoGlobalTimer},
+    inter::InterSession,
+};
+
+/// 行棋方计时, 最后几秒报警, 超时判负
+pub fn tick_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut data: ResMut<Data>,
+    mut gameover: EventWriter<GameoverEvent>,
+    sound_handles: Res<public::asset::Sounds>,
+    session: Option<Res<InterSession>>,
+) {
+    // 打谱不计时
+    if data.mode == Some(GameMode::DeduceGame) {
+        return;
+    }
+    // 联机对方断线等待重连时暂停计时
+    if session.is_some_and(|session| session.offline.is_some()) {
+        return;
+    }
+    let side = match data.current_side {
+        Some(side) => side,
+        None => return,
+    };
+    match data.get_current_player().tick(time.delta()) {
+        TimerState::Normal => {}
+        TimerState::Alarm => {
+            commands.spawn(super::audio::play_once(sound_handles.alarm.clone()));
+        }
+        // 观战时由主场方判定超时
+        TimerState::Timeout if data.mode == Some(GameMode::WatchGame) => {}
+        TimerState::Timeout => {
+            info!("{}方超时", side.name());
+            gameover.send(GameoverEvent(side.opponent().winner()));
+        }
+    }
+}
+
+/// 刷新双方局时与步时(读秒)
+pub fn display_system(
+    data: Res<Data>,
+    mut global_q: Query<
+        (&player::Player, &mut Text),
+        (With<PlayerInfoGlobalTimer>, Without<PlayerInfoCurrentTimer>),
+    >,
+    mut current_q: Query<(&player::Player, &mut Text), With<PlayerInfoCurrentTimer>>,
+) {
+    let get_player = |side: Side| match side {
+        Side::White => data.white_player,
+        Side::Black => data.black_player,
+    };
+    for (player, mut text) in global_q.iter_mut() {
+        text.sections[0].value = get_player(player.side).get_global_timer();
+    }
+    for (player, mut text) in current_q.iter_mut() {
+        text.sections[0].value = get_player(player.side).get_current_timer();
+    }
+}
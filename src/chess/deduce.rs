@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+
+use crate::{
+    event::{BroadRefreshEvent, DeduceAction, DeduceEvent, PromptEvent},
+    game::Data,
+    player,
+    public::{self, BroadEntitys},
+};
+
+use super::info::{self, PlayerInfoAction};
+
+/// 打谱推演: 前进后退与引擎分析开关
+pub fn event_listen(
+    mut commands: Commands,
+    mut events: EventReader<DeduceEvent>,
+    mut data: ResMut<Data>,
+    mut entitys: ResMut<BroadEntitys>,
+    mut refresh: EventWriter<BroadRefreshEvent>,
+    mut prompt: EventWriter<PromptEvent>,
+    mut action_q: Query<(&player::Player, &mut Text), With<PlayerInfoAction>>,
+    sound_handles: Res<public::asset::Sounds>,
+) {
+    for event in events.iter() {
+        let moved = match event.0 {
+            DeduceAction::Back => data.undo_move().is_some(),
+            DeduceAction::Forward => data.redo_move().is_some(),
+            DeduceAction::Analysis => {
+                data.analysis = !data.analysis;
+                info!("打谱分析: {}", data.analysis);
+                match data.analysis {
+                    true => prompt.send(PromptEvent),
+                    // 刷新棋盘时清除推荐走法
+                    false => refresh.send(BroadRefreshEvent),
+                }
+                return;
+            }
+        };
+        if !moved {
+            info!("没有可以前进或后退的棋");
+            commands.spawn(super::audio::play_once(sound_handles.invalid.clone()));
+            return;
+        }
+
+        // 取消选择, 选中的棋子会在刷新棋盘时一并销毁
+        data.selected = None;
+        entitys.selected = None;
+        info!("打谱 round:{} side:{:?}", data.round, data.current_side);
+        refresh.send(BroadRefreshEvent);
+        info::update_action_text(data.current_side.unwrap(), &mut action_q);
+        commands.spawn(super::audio::play_once(sound_handles.go.clone()));
+        return;
+    }
+}
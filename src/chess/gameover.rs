@@ -2,6 +2,7 @@ use crate::{
     component::piece::Side,
     event::GameoverEvent,
     game::{Data, GameMode},
+    net::{Message, NetConnection},
     public,
     status::ChessState,
 };
@@ -15,11 +16,21 @@ pub fn event_listen(
     sound_handles: Res<public::asset::Sounds>,
     image_handles: Res<public::asset::Images>,
     mut chess_state: ResMut<NextState<ChessState>>,
+    connection: Option<Res<NetConnection>>,
 ) {
     for event in events.iter() {
+        // 主场方把对局结果告知观众
+        if let (Some(GameMode::InterGame), Some(connection)) = (data.mode, &connection) {
+            connection.broadcast(Message::Over(match event.0 {
+                chessai::pregen::Winner::White => Some(Side::White),
+                chessai::pregen::Winner::Black => Some(Side::Black),
+                chessai::pregen::Winner::Tie => None,
+            }));
+        }
         let (sound, image) = match event.0 {
             chessai::pregen::Winner::White => {
                 info!("红方胜利");
+                data.result = Some(chessai::pregen::Winner::White);
                 match data.mode.unwrap() {
                     GameMode::AiGame => match data.ai_side.unwrap() {
                         Side::White => {
@@ -27,17 +38,21 @@ pub fn event_listen(
                         }
                         Side::Black => (sound_handles.win.clone(), image_handles.flag_win.clone()),
                     },
-                    GameMode::DeduceGame => {
-                        (sound_handles.win.clone(), image_handles.flag_win.clone())
-                    }
-                    GameMode::InterGame => {
-                        // todo
+                    // 打谱与观战没有本方, 只显示胜利方
+                    GameMode::DeduceGame | GameMode::WatchGame => {
                         (sound_handles.win.clone(), image_handles.flag_win.clone())
                     }
+                    GameMode::InterGame => match data.away_side() {
+                        Some(Side::White) => {
+                            (sound_handles.loss.clone(), image_handles.flag_loss.clone())
+                        }
+                        _ => (sound_handles.win.clone(), image_handles.flag_win.clone()),
+                    },
                 }
             }
             chessai::pregen::Winner::Black => {
                 info!("黑方胜利");
+                data.result = Some(chessai::pregen::Winner::Black);
                 match data.mode.unwrap() {
                     GameMode::AiGame => match data.ai_side.unwrap() {
                         Side::White => (sound_handles.win.clone(), image_handles.flag_win.clone()),
@@ -45,17 +60,21 @@ pub fn event_listen(
                             (sound_handles.loss.clone(), image_handles.flag_loss.clone())
                         }
                     },
-                    GameMode::DeduceGame => {
-                        (sound_handles.win.clone(), image_handles.flag_win.clone())
-                    }
-                    GameMode::InterGame => {
-                        // todo
+                    // 打谱与观战没有本方, 只显示胜利方
+                    GameMode::DeduceGame | GameMode::WatchGame => {
                         (sound_handles.win.clone(), image_handles.flag_win.clone())
                     }
+                    GameMode::InterGame => match data.away_side() {
+                        Some(Side::Black) => {
+                            (sound_handles.loss.clone(), image_handles.flag_loss.clone())
+                        }
+                        _ => (sound_handles.win.clone(), image_handles.flag_win.clone()),
+                    },
                 }
             }
             chessai::pregen::Winner::Tie => {
                 info!("和棋");
+                data.result = Some(chessai::pregen::Winner::Tie);
                 (sound_handles.draw.clone(), image_handles.flag_draw.clone())
             }
         };
@@ -1,7 +1,7 @@
-use crate::component::PlayerInfo;
-use crate::event::{EventAction, GameChangeEvent};
-use crate::game::Data;
-use crate::public;
+use crate::component::{piece::Side, PlayerInfo};
+use crate::event::{BroadRefreshEvent, EventAction, GameChangeEvent};
+use crate::game::{Data, GameMode};
+use crate::{player, public};
 use bevy::prelude::*;
 
 // 玩家信息标题
@@ -20,6 +20,10 @@ pub struct PlayerInfoGlobalTimer;
 #[derive(Component, Debug, Default, Clone)]
 pub struct PlayerInfoCurrentTimer;
 
+// 玩家信息面板(信息框/行动信息), 随棋盘翻转左右交换
+#[derive(Component, Debug, Default, Clone)]
+pub struct PlayerInfoPanel;
+
 pub fn event_listen(
     mut events: EventReader<GameChangeEvent>,
     mut commands: Commands,
@@ -33,312 +37,9 @@ pub fn event_listen(
             EventAction::Spawn => {
                 info!("加载游戏玩家信息");
                 // 黑色玩家信息框
-                commands
-                    .spawn((
-                        NodeBundle {
-                            style: Style {
-                                position_type: PositionType::Absolute,
-                                left: Val::Percent(0_f32),
-                                bottom: Val::Percent(0_f32),
-                                width: Val::Percent(50_f32),
-                                height: Val::Percent(100_f32),
-                                ..default()
-                            },
-                            ..default()
-                        },
-                        PlayerInfo,
-                        data.black_player,
-                    ))
-                    .with_children(|parent| {
-                        parent
-                            .spawn(ImageBundle {
-                                image: UiImage::new(image_handles.player_frame.clone()),
-                                style: Style {
-                                    position_type: PositionType::Absolute,
-                                    top: Val::Percent(8_f32),
-                                    left: Val::Percent(10_f32),
-                                    width: Val::Px(200_f32),
-                                    height: Val::Px(250_f32),
-                                    ..default()
-                                },
-                                ..default()
-                            })
-                            .with_children(|parent| {
-                                // 头像
-                                parent.spawn(ImageBundle {
-                                    image: UiImage::new(image_handles.black_avatar.clone()),
-                                    style: Style {
-                                        position_type: PositionType::Relative,
-                                        top: Val::Percent(15_f32),
-                                        left: Val::Percent(25_f32),
-                                        width: Val::Percent(50_f32),
-                                        height: Val::Percent(40_f32),
-                                        ..default()
-                                    },
-                                    ..default()
-                                });
-
-                                // 标题
-                                parent.spawn((
-                                    TextBundle {
-                                        style: Style {
-                                            left: Val::Percent(-10_f32),
-                                            ..default()
-                                        },
-                                        text: Text::from_section(
-                                            "黑方",
-                                            TextStyle {
-                                                font: fonts.xiaoli.clone(),
-                                                font_size: 25_f32,
-                                                color: Color::ANTIQUE_WHITE,
-                                            },
-                                        ),
-                                        ..default()
-                                    },
-                                    PlayerInfoTitle,
-                                    data.black_player,
-                                ));
-
-                                // 局计时器
-                                parent.spawn((
-                                    TextBundle {
-                                        style: Style {
-                                            position_type: PositionType::Absolute,
-                                            top: Val::Percent(65_f32),
-                                            left: Val::Percent(22_f32),
-                                            ..default()
-                                        },
-                                        text: Text::from_section(
-                                            // data.black_player.get_global_timer(),
-                                            "局时: 00:00",
-                                            TextStyle {
-                                                font: fonts.wenkai.clone(),
-                                                font_size: 24_f32,
-                                                color: Color::ANTIQUE_WHITE,
-                                            },
-                                        ),
-                                        ..default()
-                                    }
-                                    .with_no_wrap(),
-                                    PlayerInfoGlobalTimer,
-                                    data.black_player,
-                                ));
-
-                                // 步计时器
-                                parent.spawn((
-                                    TextBundle {
-                                        style: Style {
-                                            position_type: PositionType::Absolute,
-                                            top: Val::Percent(80_f32),
-                                            left: Val::Percent(22_f32),
-                                            ..default()
-                                        },
-                                        text: Text::from_section(
-                                            // data.black_player.get_current_timer(),
-                                            "步时: 00:00",
-                                            TextStyle {
-                                                font: fonts.wenkai.clone(),
-                                                font_size: 24_f32,
-                                                color: Color::ANTIQUE_WHITE,
-                                            },
-                                        ),
-                                        ..default()
-                                    }
-                                    .with_no_wrap(),
-                                    PlayerInfoCurrentTimer,
-                                    data.black_player,
-                                ));
-                            });
-
-                        // 黑方行动信息
-                        parent
-                            .spawn(ImageBundle {
-                                image: UiImage::new(image_handles.popup.clone()),
-                                style: Style {
-                                    position_type: PositionType::Absolute,
-                                    top: Val::Percent(50_f32),
-                                    left: Val::Percent(10_f32),
-                                    width: Val::Px(200_f32),
-                                    height: Val::Px(60_f32),
-                                    ..default()
-                                },
-                                ..default()
-                            })
-                            .with_children(|parent| {
-                                parent.spawn((
-                                    TextBundle::from_section(
-                                        "空闲中",
-                                        TextStyle {
-                                            font: fonts.xiaoli.clone(),
-                                            font_size: 24_f32,
-                                            color: Color::DARK_GREEN,
-                                        },
-                                    )
-                                    .with_style(Style {
-                                        position_type: PositionType::Absolute,
-                                        left: Val::Percent(32_f32),
-                                        top: Val::Percent(20_f32),
-                                        ..default()
-                                    }),
-                                    PlayerInfoAction,
-                                    data.black_player,
-                                ));
-                            });
-                    });
-
+                spawn_player_info(&mut commands, data.black_player, &data, &image_handles, &fonts);
                 // 红方信息框
-                commands
-                    .spawn((
-                        NodeBundle {
-                            style: Style {
-                                position_type: PositionType::Absolute,
-                                right: Val::Percent(0_f32),
-                                bottom: Val::Percent(0_f32),
-                                width: Val::Percent(50_f32),
-                                height: Val::Percent(100_f32),
-                                ..default()
-                            },
-                            ..default()
-                        },
-                        PlayerInfo,
-                        data.white_player,
-                    ))
-                    .with_children(|parent| {
-                        parent
-                            .spawn(ImageBundle {
-                                image: UiImage::new(image_handles.player_frame.clone()),
-                                style: Style {
-                                    position_type: PositionType::Absolute,
-                                    top: Val::Percent(8_f32),
-                                    right: Val::Percent(10_f32),
-                                    width: Val::Px(200_f32),
-                                    height: Val::Px(250_f32),
-                                    ..default()
-                                },
-                                ..default()
-                            })
-                            .with_children(|parent| {
-                                // 头像
-                                parent.spawn(ImageBundle {
-                                    image: UiImage::new(image_handles.white_avatar.clone()),
-                                    style: Style {
-                                        position_type: PositionType::Relative,
-                                        top: Val::Percent(15_f32),
-                                        left: Val::Percent(25_f32),
-                                        width: Val::Percent(50_f32),
-                                        height: Val::Percent(40_f32),
-                                        ..default()
-                                    },
-                                    ..default()
-                                });
-
-                                // 标题
-                                parent.spawn((
-                                    TextBundle {
-                                        style: Style {
-                                            left: Val::Percent(-10_f32),
-                                            ..default()
-                                        },
-                                        text: Text::from_section(
-                                            "红方",
-                                            TextStyle {
-                                                font: fonts.xiaoli.clone(),
-                                                font_size: 25_f32,
-                                                color: Color::ANTIQUE_WHITE,
-                                            },
-                                        ),
-                                        ..default()
-                                    },
-                                    PlayerInfoTitle,
-                                    data.white_player,
-                                ));
-
-                                // 局计时器
-                                parent.spawn((
-                                    TextBundle {
-                                        style: Style {
-                                            position_type: PositionType::Absolute,
-                                            top: Val::Percent(65_f32),
-                                            left: Val::Percent(22_f32),
-                                            ..default()
-                                        },
-                                        text: Text::from_section(
-                                            // data.white_player.get_global_timer(),
-                                            "局时: 00:00",
-                                            TextStyle {
-                                                font: fonts.wenkai.clone(),
-                                                font_size: 24_f32,
-                                                color: Color::ANTIQUE_WHITE,
-                                            },
-                                        ),
-                                        ..default()
-                                    }
-                                    .with_no_wrap(),
-                                    PlayerInfoGlobalTimer,
-                                    data.white_player,
-                                ));
-
-                                // 步计时器
-                                parent.spawn((
-                                    TextBundle {
-                                        style: Style {
-                                            position_type: PositionType::Absolute,
-                                            top: Val::Percent(80_f32),
-                                            left: Val::Percent(22_f32),
-                                            ..default()
-                                        },
-                                        text: Text::from_section(
-                                            // data.white_player.get_current_timer(),
-                                            "步时: 00:00",
-                                            TextStyle {
-                                                font: fonts.wenkai.clone(),
-                                                font_size: 24_f32,
-                                                color: Color::ANTIQUE_WHITE,
-                                            },
-                                        ),
-                                        ..default()
-                                    }
-                                    .with_no_wrap(),
-                                    PlayerInfoCurrentTimer,
-                                    data.white_player,
-                                ));
-                            });
-
-                        // 红方行动信息
-                        parent
-                            .spawn(ImageBundle {
-                                image: UiImage::new(image_handles.popup.clone()),
-                                style: Style {
-                                    position_type: PositionType::Absolute,
-                                    top: Val::Percent(50_f32),
-                                    right: Val::Percent(10_f32),
-                                    width: Val::Px(200_f32),
-                                    height: Val::Px(60_f32),
-                                    ..default()
-                                },
-                                ..default()
-                            })
-                            .with_children(|parent| {
-                                parent.spawn((
-                                    TextBundle::from_section(
-                                        "空闲中",
-                                        TextStyle {
-                                            font: fonts.xiaoli.clone(),
-                                            font_size: 24_f32,
-                                            color: Color::DARK_GREEN,
-                                        },
-                                    )
-                                    .with_style(Style {
-                                        position_type: PositionType::Absolute,
-                                        left: Val::Percent(32_f32),
-                                        top: Val::Percent(20_f32),
-                                        ..default()
-                                    }),
-                                    PlayerInfoAction,
-                                    data.white_player,
-                                ));
-                            });
-                    });
+                spawn_player_info(&mut commands, data.white_player, &data, &image_handles, &fonts);
             }
             EventAction::Hidden => {
                 info!("隐藏游戏玩家信息");
@@ -361,3 +62,251 @@ pub fn event_listen(
         }
     }
 }
+
+/// 棋盘刷新时按翻转状态摆放双方信息框
+pub fn refresh_listen(
+    mut events: EventReader<BroadRefreshEvent>,
+    data: Res<Data>,
+    mut info_q: Query<(&player::Player, &mut Style), With<PlayerInfo>>,
+    mut panel_q: Query<(&Parent, &mut Style), (With<PlayerInfoPanel>, Without<PlayerInfo>)>,
+) {
+    for _ in events.iter() {
+        for (player, mut style) in info_q.iter_mut() {
+            set_horizontal(&mut style, info_on_left(player.side, data.flipped), 0_f32);
+        }
+        for (parent, mut style) in panel_q.iter_mut() {
+            if let Ok((player, _)) = info_q.get(parent.get()) {
+                set_horizontal(&mut style, info_on_left(player.side, data.flipped), 10_f32);
+            }
+        }
+        return;
+    }
+}
+
+/// 未翻转时黑方信息在左侧, 翻转后红方信息在左侧
+fn info_on_left(side: Side, flipped: bool) -> bool {
+    (side == Side::Black) != flipped
+}
+
+fn set_horizontal(style: &mut Style, on_left: bool, percent: f32) {
+    if on_left {
+        style.left = Val::Percent(percent);
+        style.right = Val::Auto;
+    } else {
+        style.left = Val::Auto;
+        style.right = Val::Percent(percent);
+    }
+}
+
+fn spawn_player_info(
+    commands: &mut Commands,
+    player: player::Player,
+    data: &Data,
+    image_handles: &public::asset::Images,
+    fonts: &public::asset::Fonts,
+) {
+    let on_left = info_on_left(player.side, data.flipped);
+    let avatar = match player.side {
+        Side::White => image_handles.white_avatar.clone(),
+        Side::Black => image_handles.black_avatar.clone(),
+    };
+    let mut root_style = Style {
+        position_type: PositionType::Absolute,
+        bottom: Val::Percent(0_f32),
+        width: Val::Percent(50_f32),
+        height: Val::Percent(100_f32),
+        ..default()
+    };
+    set_horizontal(&mut root_style, on_left, 0_f32);
+    let mut frame_style = Style {
+        position_type: PositionType::Absolute,
+        top: Val::Percent(8_f32),
+        width: Val::Px(200_f32),
+        height: Val::Px(250_f32),
+        ..default()
+    };
+    set_horizontal(&mut frame_style, on_left, 10_f32);
+    let mut popup_style = Style {
+        position_type: PositionType::Absolute,
+        top: Val::Percent(50_f32),
+        width: Val::Px(200_f32),
+        height: Val::Px(60_f32),
+        ..default()
+    };
+    set_horizontal(&mut popup_style, on_left, 10_f32);
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: root_style,
+                ..default()
+            },
+            PlayerInfo,
+            player,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    ImageBundle {
+                        image: UiImage::new(image_handles.player_frame.clone()),
+                        style: frame_style,
+                        ..default()
+                    },
+                    PlayerInfoPanel,
+                ))
+                .with_children(|parent| {
+                    // 头像
+                    parent.spawn(ImageBundle {
+                        image: UiImage::new(avatar),
+                        style: Style {
+                            position_type: PositionType::Relative,
+                            top: Val::Percent(15_f32),
+                            left: Val::Percent(25_f32),
+                            width: Val::Percent(50_f32),
+                            height: Val::Percent(40_f32),
+                            ..default()
+                        },
+                        ..default()
+                    });
+
+                    // 标题
+                    parent.spawn((
+                        TextBundle {
+                            style: Style {
+                                left: Val::Percent(-10_f32),
+                                ..default()
+                            },
+                            text: Text::from_section(
+                                player.title(),
+                                TextStyle {
+                                    font: fonts.xiaoli.clone(),
+                                    font_size: 25_f32,
+                                    color: Color::ANTIQUE_WHITE,
+                                },
+                            ),
+                            ..default()
+                        },
+                        PlayerInfoTitle,
+                        player,
+                    ));
+
+                    // 局计时器
+                    parent.spawn((
+                        TextBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                top: Val::Percent(65_f32),
+                                left: Val::Percent(22_f32),
+                                ..default()
+                            },
+                            text: Text::from_section(
+                                player.get_global_timer(),
+                                TextStyle {
+                                    font: fonts.wenkai.clone(),
+                                    font_size: 24_f32,
+                                    color: Color::ANTIQUE_WHITE,
+                                },
+                            ),
+                            ..default()
+                        }
+                        .with_no_wrap(),
+                        PlayerInfoGlobalTimer,
+                        player,
+                    ));
+
+                    // 步计时器
+                    parent.spawn((
+                        TextBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                top: Val::Percent(80_f32),
+                                left: Val::Percent(22_f32),
+                                ..default()
+                            },
+                            text: Text::from_section(
+                                player.get_current_timer(),
+                                TextStyle {
+                                    font: fonts.wenkai.clone(),
+                                    font_size: 24_f32,
+                                    color: Color::ANTIQUE_WHITE,
+                                },
+                            ),
+                            ..default()
+                        }
+                        .with_no_wrap(),
+                        PlayerInfoCurrentTimer,
+                        player,
+                    ));
+                });
+
+            // 行动信息
+            parent
+                .spawn((
+                    ImageBundle {
+                        image: UiImage::new(image_handles.popup.clone()),
+                        style: popup_style,
+                        ..default()
+                    },
+                    PlayerInfoPanel,
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "空闲中",
+                            TextStyle {
+                                font: fonts.xiaoli.clone(),
+                                font_size: 24_f32,
+                                color: Color::DARK_GREEN,
+                            },
+                        )
+                        .with_style(Style {
+                            position_type: PositionType::Absolute,
+                            left: Val::Percent(32_f32),
+                            top: Val::Percent(20_f32),
+                            ..default()
+                        }),
+                        PlayerInfoAction,
+                        player,
+                    ));
+                });
+
+            // 上方玩家信息框下方为棋谱
+            if on_left {
+                super::record::spawn_panel(parent, on_left, fonts);
+            }
+            // 联机对弈时本方信息框下方为聊天面板
+            if data.mode == Some(GameMode::InterGame) && matches!(player.id, player::Id::Home) {
+                super::chat::spawn_panel(parent, on_left, fonts);
+            }
+        });
+}
+
+/// 刷新双方行动信息
+pub fn update_action_text(
+    current_side: Side,
+    action_q: &mut Query<(&player::Player, &mut Text), With<PlayerInfoAction>>,
+) {
+    for (player, mut text) in action_q.iter_mut() {
+        if current_side == player.side {
+            text.sections[0].value = String::from("思考中");
+            text.sections[0].style.color = Color::ORANGE_RED;
+        } else {
+            text.sections[0].value = String::from("空闲中");
+            text.sections[0].style.color = Color::DARK_GREEN;
+        }
+    }
+}
+
+/// 刷新双方标题
+pub fn update_title_text(
+    data: &Data,
+    title_q: &mut Query<(&mut player::Player, &mut Text), With<PlayerInfoTitle>>,
+) {
+    for (mut player, mut text) in title_q.iter_mut() {
+        *player = match player.side {
+            Side::White => data.white_player,
+            Side::Black => data.black_player,
+        };
+        text.sections[0].value = player.title();
+    }
+}
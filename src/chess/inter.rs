@@ -0,0 +1,345 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use chessai::position::pos2iccs;
+
+use crate::{
+    event::{BroadRefreshEvent, EventAction, GameChangeEvent, GameoverEvent, SwithPlayerEvent},
+    game::{Data, GameMode},
+    net::{discovery::Advertiser, protocol, Message, NetConnection, NetEvent, Resync},
+    player,
+    public::{self, BroadEntitys, Pos},
+    status::ChessState,
+};
+
+use super::{
+    chat::{ChatHistory, ChatSender},
+    info::{self, PlayerInfoAction},
+    peace::PeacePopup,
+};
+
+// 对方断线后等待重连的时间, 超时判负
+pub const RECONNECT_WINDOW: Duration = Duration::from_secs(60);
+pub const OFFLINE_TEXT: &str = "对方断线";
+// 走法超过确认时间未确认则重发
+pub const ACK_TIMEOUT: Duration = Duration::from_secs(3);
+pub const UNACKED_TEXT: &str = "等待确认";
+
+/// 主场方显示的观众人数
+#[derive(Component)]
+pub struct SpectatorCount;
+
+/// 联机对局会话: 走法确认与断线重连
+#[derive(Resource, Default)]
+pub struct InterSession {
+    // 等待对方确认的走法序号
+    pub unacked: Option<usize>,
+    // 距上次发送走法的时长
+    pub waiting: Duration,
+    // 对方断线的时长, 在线时为None
+    pub offline: Option<Duration>,
+}
+
+/// 把本方最后一步发给对方, 等待确认
+pub fn send_move(connection: &NetConnection, session: &mut InterSession, data: &Data) {
+    let seq = data.history.len();
+    let (src, dst) = data.get_last_move().unwrap();
+    let message = Message::Move(seq, pos2iccs(src.row, src.col, dst.row, dst.col));
+    connection.broadcast(message.clone());
+    connection.send(message);
+    session.unacked = Some(seq);
+    session.waiting = Duration::ZERO;
+}
+
+/// 全部走法, 用于重连后同步
+fn move_list(data: &Data) -> Vec<String> {
+    data.history
+        .iter()
+        .map(|step| pos2iccs(step.src.row, step.src.col, step.dst.row, step.dst.col))
+        .collect()
+}
+
+/// 联机对弈: 处理对方的走法、求和与认输, 断线后等待重连并同步对局
+pub fn receive_system(
+    mut commands: Commands,
+    mut data: ResMut<Data>,
+    mut entitys: ResMut<BroadEntitys>,
+    mut session: ResMut<InterSession>,
+    connection: Res<NetConnection>,
+    state: Res<State<ChessState>>,
+    mut refresh: EventWriter<BroadRefreshEvent>,
+    mut gameover: EventWriter<GameoverEvent>,
+    mut swith_player: EventWriter<SwithPlayerEvent>,
+    fonts: Res<public::asset::Fonts>,
+    sound_handles: Res<public::asset::Sounds>,
+    image_handles: Res<public::asset::Images>,
+    mut action_q: Query<(&player::Player, &mut Text), With<PlayerInfoAction>>,
+    q_popup: Query<Entity, With<PeacePopup>>,
+    mut chat: Option<ResMut<ChatHistory>>,
+) {
+    if data.mode != Some(GameMode::InterGame) {
+        return;
+    }
+    let away_side = data.away_side().unwrap();
+    while let Some(event) = connection.poll() {
+        let message = match event {
+            NetEvent::Message(message) => message,
+            NetEvent::Connected => {
+                // 重连后双方交换走法与局面
+                info!("连接恢复, 同步对局");
+                connection.send(Message::Sync(move_list(&data), data.to_fen()));
+                continue;
+            }
+            NetEvent::Disconnected => {
+                if *state.get() == ChessState::Gameover {
+                    commands.remove_resource::<NetConnection>();
+                    return;
+                }
+                info!("对方断线, 等待重连");
+                session.offline = Some(Duration::ZERO);
+                continue;
+            }
+            NetEvent::Spectator => {
+                // 新观众从开局同步全部走法, 已在观战的观众重新同步不受影响
+                let home_side = away_side.opponent();
+                connection.broadcast(Message::Start(home_side, data.time_control.index()));
+                connection.broadcast(Message::Sync(move_list(&data), data.to_fen()));
+                continue;
+            }
+        };
+        info!("收到 {}", message.encode());
+        // 对局结束后仍可以聊天
+        if let Message::Chat(text) = message {
+            if let Some(chat) = chat.as_mut() {
+                chat.push(ChatSender::Away, text);
+            }
+            continue;
+        }
+        if *state.get() == ChessState::Gameover {
+            continue;
+        }
+        let (seq, iccs) = match message {
+            Message::Move(seq, iccs) => {
+                // 同步时已经走过的重复走法, 只需确认
+                if seq <= data.history.len() {
+                    connection.send(Message::Ack(seq));
+                    continue;
+                }
+                (seq, iccs)
+            }
+            Message::Ack(seq) => {
+                if session.unacked == Some(seq) {
+                    session.unacked = None;
+                    info::update_action_text(data.current_side.unwrap(), &mut action_q);
+                }
+                continue;
+            }
+            Message::Sync(moves, fen) => {
+                let resync = protocol::resync(&move_list(&data), &moves);
+                if resync == Resync::Diverged {
+                    warn!("对局记录不一致, 忽略同步");
+                    continue;
+                }
+                info!("对方重新连接");
+                session.offline = None;
+                info::update_action_text(data.current_side.unwrap(), &mut action_q);
+                match resync {
+                    Resync::Behind(iccs) => (moves.len(), iccs),
+                    Resync::Ahead => {
+                        // 补发对方没有收到的走法
+                        send_move(&connection, &mut session, &data);
+                        continue;
+                    }
+                    _ => {
+                        session.unacked = None;
+                        if fen.split_whitespace().next() != data.to_fen().split_whitespace().next()
+                        {
+                            warn!("双方局面不一致 {fen}");
+                        }
+                        continue;
+                    }
+                }
+            }
+            Message::DrawOffer => {
+                if q_popup.is_empty() {
+                    super::peace::spawn_popup(&mut commands, away_side, &fonts, &image_handles);
+                }
+                continue;
+            }
+            Message::DrawAccept => {
+                gameover.send(GameoverEvent(chessai::pregen::Winner::Tie));
+                continue;
+            }
+            Message::DrawDecline => {
+                for (player, mut text) in action_q.iter_mut() {
+                    if player.side == away_side {
+                        text.sections[0].value = String::from("拒绝和棋");
+                        text.sections[0].style.color = Color::ORANGE_RED;
+                    }
+                }
+                commands.spawn(super::audio::play_once(sound_handles.invalid.clone()));
+                continue;
+            }
+            Message::Resign => {
+                info!("对方认输");
+                gameover.send(GameoverEvent(away_side.opponent().winner()));
+                continue;
+            }
+            _ => continue,
+        };
+
+        // 与本地行棋相同, 由引擎校验走法; 序号不连续的走法等待重连同步
+        let ((src_row, src_col), (dst_row, dst_col)) = data.parse_route(iccs.clone());
+        let (src, dst) = (Pos::new(src_row, src_col), Pos::new(dst_row, dst_col));
+        if seq != data.history.len() + 1
+            || data.chess_state() != ChessState::AwayPlay
+            || !data.can_move(src, dst)
+        {
+            warn!("对方走法不合法 {seq} {iccs}");
+            continue;
+        }
+        data.selected = None;
+        entitys.selected = None;
+        data.make_move(src, dst);
+        connection.send(Message::Ack(seq));
+        connection.broadcast(Message::Move(seq, iccs));
+        refresh.send(BroadRefreshEvent);
+        super::chess::finish_move(
+            &mut data,
+            &mut commands,
+            &sound_handles,
+            &mut gameover,
+            &mut swith_player,
+        );
+    }
+}
+
+/// 对方断线: 显示重连倒计时, 超过等待时间判对方负
+pub fn reconnect_system(
+    time: Res<Time>,
+    data: Res<Data>,
+    state: Res<State<ChessState>>,
+    mut session: ResMut<InterSession>,
+    mut gameover: EventWriter<GameoverEvent>,
+    mut action_q: Query<(&player::Player, &mut Text), With<PlayerInfoAction>>,
+) {
+    let away_side = match (session.offline, data.away_side()) {
+        (Some(_), Some(away_side)) if *state.get() != ChessState::Gameover => away_side,
+        _ => return,
+    };
+    let offline = session.offline.unwrap() + time.delta();
+    session.offline = Some(offline);
+    if offline >= RECONNECT_WINDOW {
+        info!("对方断线超时, 判负");
+        session.offline = None;
+        gameover.send(GameoverEvent(away_side.opponent().winner()));
+        return;
+    }
+    let remaining = (RECONNECT_WINDOW - offline).as_secs() + 1;
+    for (player, mut text) in action_q.iter_mut() {
+        if player.side == away_side {
+            text.sections[0].value = format!("{OFFLINE_TEXT} {remaining}s");
+            text.sections[0].style.color = Color::ORANGE_RED;
+        }
+    }
+}
+
+/// 走法超时未确认: 重发给对方并显示等待确认, 断线时由重连同步补发
+pub fn ack_system(
+    time: Res<Time>,
+    data: Res<Data>,
+    state: Res<State<ChessState>>,
+    connection: Res<NetConnection>,
+    mut session: ResMut<InterSession>,
+    mut action_q: Query<(&player::Player, &mut Text), With<PlayerInfoAction>>,
+) {
+    let (seq, step) = match session.unacked {
+        Some(seq) if session.offline.is_none() && *state.get() != ChessState::Gameover => {
+            match data.history.get(seq - 1) {
+                Some(step) => (seq, *step),
+                None => return,
+            }
+        }
+        _ => return,
+    };
+    session.waiting += time.delta();
+    if session.waiting < ACK_TIMEOUT {
+        return;
+    }
+    session.waiting = Duration::ZERO;
+    let iccs = pos2iccs(step.src.row, step.src.col, step.dst.row, step.dst.col);
+    warn!("走法未确认, 重发 {seq} {iccs}");
+    connection.send(Message::Move(seq, iccs));
+    for (player, mut text) in action_q.iter_mut() {
+        if Some(player.side) == data.away_side() {
+            text.sections[0].value = String::from(UNACKED_TEXT);
+            text.sections[0].style.color = Color::ORANGE_RED;
+        }
+    }
+}
+
+/// 刷新观众人数
+pub fn spectator_system(
+    connection: Res<NetConnection>,
+    mut text_q: Query<&mut Text, With<SpectatorCount>>,
+) {
+    for mut text in text_q.iter_mut() {
+        let value = format!("观战: {}人", connection.spectator_count());
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+/// 主场方显示观众人数, 返回主菜单时断开连接并停止局域网广播
+pub fn event_listen(
+    mut commands: Commands,
+    mut events: EventReader<GameChangeEvent>,
+    fonts: Res<public::asset::Fonts>,
+    connection: Option<Res<NetConnection>>,
+    mut query: Query<(Entity, &mut Visibility), With<SpectatorCount>>,
+) {
+    for event in events.iter() {
+        match event.0 {
+            EventAction::Spawn => {
+                if connection.as_ref().is_some_and(|connection| connection.host) {
+                    commands.spawn((
+                        TextBundle::from_section(
+                            "观战: 0人",
+                            TextStyle {
+                                font: fonts.wenkai.clone(),
+                                font_size: 24_f32,
+                                color: Color::ANTIQUE_WHITE,
+                            },
+                        )
+                        .with_style(Style {
+                            position_type: PositionType::Absolute,
+                            top: Val::Px(8_f32),
+                            right: Val::Px(16_f32),
+                            ..default()
+                        }),
+                        SpectatorCount,
+                    ));
+                }
+            }
+            EventAction::Hidden => {
+                for (_, mut visibility) in query.iter_mut() {
+                    *visibility = Visibility::Hidden;
+                }
+            }
+            EventAction::Visibie => {
+                for (_, mut visibility) in query.iter_mut() {
+                    *visibility = Visibility::Inherited;
+                }
+            }
+            EventAction::Despawn => {
+                for (entity, _) in query.iter_mut() {
+                    commands.entity(entity).despawn_recursive();
+                }
+                commands.remove_resource::<NetConnection>();
+                commands.remove_resource::<InterSession>();
+                commands.remove_resource::<Advertiser>();
+            }
+        }
+    }
+}
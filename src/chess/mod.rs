@@ -2,18 +2,32 @@ mod audio;
 mod button;
 
 use crate::{
-    event::{GameChangeEvent, GameoverEvent, SwithPlayerEvent},
+    event::{
+        BroadRefreshEvent, DeduceEvent, GameChangeEvent, GameoverEvent, PeaceEvent, PositionEvent,
+        PromptEvent, RetractEvent, SwithPlayerEvent,
+    },
+    net::NetConnection,
     status::{ChessState, GameState},
 };
 use bevy::prelude::*;
 
 mod ai_chess;
 mod broad;
+mod chat;
 mod chess;
+mod clock;
+mod deduce;
 mod gameover;
 mod info;
+pub mod inter;
+mod peace;
+pub mod position;
 mod previou;
+mod prompt;
+mod record;
+mod retract;
 mod swith_player;
+mod watch;
 
 #[derive(Resource)]
 pub struct ChessPlugin;
@@ -25,6 +39,12 @@ impl Plugin for ChessPlugin {
             .add_event::<GameChangeEvent>()
             .add_event::<SwithPlayerEvent>()
             .add_event::<GameoverEvent>()
+            .add_event::<RetractEvent>()
+            .add_event::<BroadRefreshEvent>()
+            .add_event::<PromptEvent>()
+            .add_event::<PeaceEvent>()
+            .add_event::<DeduceEvent>()
+            .add_event::<PositionEvent>()
             .add_state::<ChessState>()
             .add_systems(
                 Update,
@@ -36,11 +56,27 @@ impl Plugin for ChessPlugin {
                     gameover::event_listen,
                     previou::piece_previou_animate,
                     previou::piece_previou_move,
+                    retract::event_listen,
+                    ai_chess::event_listen,
+                    prompt::event_listen,
+                    prompt::prompt_result,
+                    prompt::clear_listen,
+                    peace::event_listen,
+                    peace::peace_button_system,
+                    peace::clear_listen,
+                    deduce::event_listen,
+                    broad::refresh_listen.after(retract::event_listen).after(deduce::event_listen),
+                    info::refresh_listen,
                 ),
             )
             .add_systems(
-                Update, // 对局功能按钮
-                button::chess_button_system.run_if(in_state(ChessState::HomePlay)),
+                Update, // 对局功能按钮, AI思考、联机与观战时对方行棋也可以操作
+                button::chess_button_system.run_if(
+                    in_state(ChessState::HomePlay)
+                        .or_else(in_state(ChessState::AiPlay))
+                        .or_else(in_state(ChessState::AwayPlay))
+                        .or_else(in_state(ChessState::Gameover)),
+                ),
             )
             .add_systems(
                 Update, // 玩家棋子系统
@@ -48,10 +84,80 @@ impl Plugin for ChessPlugin {
                     .run_if(in_state(ChessState::HomePlay))
                     .after(swith_player::event_listen),
             )
+            .add_systems(
+                Update, // 对局计时, 暂停时不计时
+                (
+                    clock::tick_system.run_if(in_state(GameState::RUNNING)).run_if(
+                        in_state(ChessState::HomePlay)
+                            .or_else(in_state(ChessState::AiPlay))
+                            .or_else(in_state(ChessState::AwayPlay)),
+                    ),
+                    clock::display_system,
+                ),
+            )
+            .add_systems(
+                Update, // 联机对弈
+                (
+                    inter::receive_system
+                        .run_if(in_state(GameState::RUNNING))
+                        .run_if(resource_exists::<NetConnection>())
+                        .run_if(resource_exists::<inter::InterSession>())
+                        .after(swith_player::event_listen),
+                    inter::reconnect_system
+                        .run_if(in_state(GameState::RUNNING))
+                        .run_if(resource_exists::<inter::InterSession>()),
+                    inter::ack_system
+                        .run_if(in_state(GameState::RUNNING))
+                        .run_if(resource_exists::<NetConnection>())
+                        .run_if(resource_exists::<inter::InterSession>()),
+                    inter::spectator_system.run_if(resource_exists::<NetConnection>()),
+                    watch::receive_system
+                        .run_if(in_state(GameState::RUNNING))
+                        .run_if(resource_exists::<NetConnection>())
+                        .after(swith_player::event_listen),
+                    inter::event_listen,
+                ),
+            )
+            .add_systems(
+                Update, // 棋谱与回看
+                (
+                    record::record_system,
+                    record::scroll_system,
+                    record::preview_system.before(broad::refresh_listen),
+                    record::notation_system.before(record::record_system),
+                ),
+            )
+            .add_systems(
+                Update, // 载入与复制局面
+                (
+                    position::event_listen,
+                    position::clear_listen,
+                    position::input_system.run_if(resource_exists::<position::PositionDialog>()),
+                    position::position_button_system
+                        .run_if(resource_exists::<position::PositionDialog>())
+                        .after(position::input_system),
+                    position::render_system
+                        .run_if(resource_exists::<position::PositionDialog>())
+                        .after(position::position_button_system),
+                ),
+            )
+            .add_systems(
+                Update, // 联机聊天
+                (
+                    chat::event_listen,
+                    chat::chat_button_system.run_if(resource_exists::<chat::ChatHistory>()),
+                    chat::input_system
+                        .run_if(in_state(GameState::RUNNING))
+                        .run_if(resource_exists::<chat::ChatHistory>()),
+                    chat::scroll_system.run_if(resource_exists::<chat::ChatHistory>()),
+                    chat::render_system.run_if(resource_exists::<chat::ChatHistory>()),
+                ),
+            )
             .add_systems(
                 Update, // AI棋子系统
                 ai_chess::ai_move
                     .run_if(in_state(ChessState::AiPlay))
+                    .run_if(in_state(GameState::RUNNING))
                     .after(swith_player::event_listen),
             );
     }
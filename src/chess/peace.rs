@@ -0,0 +1,249 @@
+use bevy::prelude::*;
+use chessai::pregen::Winner;
+
+use crate::{
+    component::piece::Side,
+    event::{EventAction, GameChangeEvent, GameoverEvent, PeaceEvent},
+    game::{Data, GameMode},
+    net::{Message, NetConnection},
+    player, public,
+};
+
+use super::info::PlayerInfoAction;
+
+// AI的局面评估分低于该值时同意和棋, 引擎分值中一个兵约10分
+pub const PEACE_ACCEPT_VALUE: isize = 20;
+
+pub const PEACE_ACCEPT_TEXT: &str = "同意";
+pub const PEACE_DECLINE_TEXT: &str = "拒绝";
+
+// 求和弹窗按钮配置
+pub const PEACE_NORMAL_BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+pub const PEACE_HOVERED_BUTTON_COLOR: Color = Color::rgb(0.30, 0.30, 0.30);
+pub const PEACE_PRESSED_BUTTON_COLOR: Color = Color::rgb(0.45, 0.45, 0.45);
+
+/// 求和弹窗
+#[derive(Component)]
+pub struct PeacePopup;
+
+/// 求和弹窗按钮
+#[derive(Component)]
+pub enum PeaceButton {
+    // 同意
+    Accept,
+    // 拒绝
+    Decline,
+}
+
+pub fn event_listen(
+    mut commands: Commands,
+    mut events: EventReader<PeaceEvent>,
+    data: Res<Data>,
+    mut gameover: EventWriter<GameoverEvent>,
+    fonts: Res<public::asset::Fonts>,
+    image_handles: Res<public::asset::Images>,
+    sound_handles: Res<public::asset::Sounds>,
+    mut action_q: Query<(&player::Player, &mut Text), With<PlayerInfoAction>>,
+    q_popup: Query<Entity, With<PeacePopup>>,
+    connection: Option<Res<NetConnection>>,
+) {
+    for _ in events.iter() {
+        let side = data.current_side.unwrap();
+        match data.mode.unwrap() {
+            GameMode::AiGame => {
+                // AI根据引擎局面评估决定是否同意
+                let ai_side = data.ai_side.unwrap();
+                let vl = data.evaluate(ai_side);
+                info!("{}方求和, AI评估: {}", side.name(), vl);
+                if vl < PEACE_ACCEPT_VALUE {
+                    gameover.send(GameoverEvent(Winner::Tie));
+                    continue;
+                }
+                for (player, mut text) in action_q.iter_mut() {
+                    if player.side == ai_side {
+                        text.sections[0].value = String::from("拒绝和棋");
+                        text.sections[0].style.color = Color::ORANGE_RED;
+                    }
+                }
+                commands.spawn(super::audio::play_once(sound_handles.invalid.clone()));
+            }
+            GameMode::InterGame => {
+                // 由对方在其界面上选择是否同意
+                if let Some(connection) = &connection {
+                    connection.send(Message::DrawOffer);
+                }
+            }
+            _ => {
+                // 由对方选择是否同意
+                if q_popup.is_empty() {
+                    spawn_popup(&mut commands, side, &fonts, &image_handles);
+                }
+            }
+        }
+    }
+}
+
+pub fn peace_button_system(
+    mut commands: Commands,
+    data: Res<Data>,
+    connection: Option<Res<NetConnection>>,
+    mut gameover: EventWriter<GameoverEvent>,
+    sound_handles: Res<public::asset::Sounds>,
+    q_popup: Query<Entity, With<PeacePopup>>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &PeaceButton),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut color, button) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = PEACE_PRESSED_BUTTON_COLOR.into();
+                let reply = match button {
+                    PeaceButton::Accept => {
+                        info!("同意和棋");
+                        gameover.send(GameoverEvent(Winner::Tie));
+                        Message::DrawAccept
+                    }
+                    PeaceButton::Decline => {
+                        info!("拒绝和棋");
+                        commands.spawn(super::audio::play_once(sound_handles.invalid.clone()));
+                        Message::DrawDecline
+                    }
+                };
+                // 联机对弈时告知对方
+                if let (Some(GameMode::InterGame), Some(connection)) = (data.mode, &connection) {
+                    connection.send(reply);
+                }
+                for entity in q_popup.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            Interaction::Hovered => {
+                *color = PEACE_HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *color = PEACE_NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+/// 重置对局时关闭求和弹窗
+pub fn clear_listen(
+    mut commands: Commands,
+    mut events: EventReader<GameChangeEvent>,
+    q_popup: Query<Entity, With<PeacePopup>>,
+) {
+    for event in events.iter() {
+        if let EventAction::Despawn = event.0 {
+            for entity in q_popup.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+pub fn spawn_popup(
+    commands: &mut Commands,
+    side: Side,
+    fonts: &public::asset::Fonts,
+    image_handles: &public::asset::Images,
+) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100_f32),
+                    height: Val::Percent(100_f32),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            PeacePopup,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(ImageBundle {
+                    image: UiImage::new(image_handles.popup.clone()),
+                    style: Style {
+                        width: Val::Px(320_f32),
+                        height: Val::Px(140_f32),
+                        flex_direction: FlexDirection::Column,
+                        justify_content: JustifyContent::SpaceEvenly,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        format!("{}方求和", side.name()),
+                        TextStyle {
+                            font: fonts.xiaoli.clone(),
+                            font_size: 28_f32,
+                            color: Color::ANTIQUE_WHITE,
+                        },
+                    ));
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Percent(100_f32),
+                                justify_content: JustifyContent::SpaceEvenly,
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            make_peace_button(
+                                parent,
+                                fonts.xiaoli.clone(),
+                                PEACE_ACCEPT_TEXT,
+                                PeaceButton::Accept,
+                            );
+                            make_peace_button(
+                                parent,
+                                fonts.xiaoli.clone(),
+                                PEACE_DECLINE_TEXT,
+                                PeaceButton::Decline,
+                            );
+                        });
+                });
+        });
+}
+
+fn make_peace_button(
+    parent: &mut ChildBuilder,
+    font: Handle<Font>,
+    text: &str,
+    button: PeaceButton,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(100_f32),
+                    height: Val::Px(40_f32),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: PEACE_NORMAL_BUTTON_COLOR.into(),
+                ..default()
+            },
+            button,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                text,
+                TextStyle {
+                    font,
+                    font_size: 24_f32,
+                    color: Color::ANTIQUE_WHITE,
+                },
+            ));
+        });
+}
@@ -0,0 +1,443 @@
+use bevy::prelude::*;
+
+use crate::{
+    clipboard,
+    event::{EventAction, GameChangeEvent, PositionEvent},
+    fen,
+    game::{Data, GameMode},
+    public,
+    status::{pending, ChessState, GameState},
+};
+
+pub const POSITION_TITLE_TEXT: &str = "载入局面";
+pub const POSITION_INPUT_HINT: &str = "输入或粘贴FEN, 回车载入";
+pub const POSITION_PASTE_TEXT: &str = "粘贴";
+pub const POSITION_COPY_TEXT: &str = "复制局面";
+pub const POSITION_LOAD_TEXT: &str = "载入";
+pub const POSITION_CANCEL_TEXT: &str = "取消";
+// FEN最大长度
+pub const POSITION_FEN_MAX_LEN: usize = 128;
+
+// 局面弹窗按钮配置
+pub const POSITION_NORMAL_BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+pub const POSITION_HOVERED_BUTTON_COLOR: Color = Color::rgb(0.30, 0.30, 0.30);
+pub const POSITION_PRESSED_BUTTON_COLOR: Color = Color::rgb(0.45, 0.45, 0.45);
+
+/// 局面弹窗
+#[derive(Component)]
+pub struct PositionPopup;
+
+/// 正在输入的FEN
+#[derive(Component)]
+pub struct PositionInputText;
+
+/// 操作结果提示
+#[derive(Component)]
+pub struct PositionStatusText;
+
+/// 局面弹窗按钮
+#[derive(Component, Clone, Copy)]
+pub enum PositionButton {
+    // 从剪贴板粘贴FEN
+    Paste,
+    // 复制当前局面的FEN
+    Copy,
+    // 载入输入的局面
+    Load,
+    // 关闭弹窗
+    Cancel,
+}
+
+/// 局面弹窗的输入内容, 弹窗打开时存在
+#[derive(Resource, Debug, Default)]
+pub struct PositionDialog {
+    // 正在输入的FEN
+    pub input: String,
+    // 操作结果提示
+    pub status: String,
+    // 回车载入
+    submit: bool,
+}
+
+impl PositionDialog {
+    /// 粘贴文本: 合并换行与多余空白, 超长截断
+    pub fn paste(&mut self, text: &str) {
+        let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+        self.input = text.chars().take(POSITION_FEN_MAX_LEN).collect();
+    }
+}
+
+/// 对局中打开局面弹窗
+pub fn event_listen(
+    mut commands: Commands,
+    mut events: EventReader<PositionEvent>,
+    fonts: Res<public::asset::Fonts>,
+    image_handles: Res<public::asset::Images>,
+    dialog: Option<Res<PositionDialog>>,
+) {
+    for _ in events.iter() {
+        if dialog.is_none() {
+            spawn_popup(&mut commands, &fonts, &image_handles, true);
+            commands.init_resource::<PositionDialog>();
+        }
+        return;
+    }
+}
+
+/// 键盘输入FEN, Ctrl+V粘贴, 回车载入
+pub fn input_system(
+    keys: Res<Input<KeyCode>>,
+    mut chars: EventReader<ReceivedCharacter>,
+    mut dialog: ResMut<PositionDialog>,
+    mut clipboard: NonSendMut<clipboard::Clipboard>,
+) {
+    let ctrl = keys.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::SuperLeft,
+        KeyCode::SuperRight,
+    ]);
+    if ctrl && keys.just_pressed(KeyCode::V) {
+        paste(&mut dialog, &mut clipboard);
+    }
+    if keys.just_pressed(KeyCode::Back) && !dialog.input.is_empty() {
+        dialog.input.pop();
+    }
+    for event in chars.iter() {
+        if !ctrl && !event.char.is_control() && dialog.input.chars().count() < POSITION_FEN_MAX_LEN
+        {
+            dialog.input.push(event.char);
+        }
+    }
+    if keys.just_pressed(KeyCode::Return) {
+        dialog.submit = true;
+    }
+}
+
+fn paste(dialog: &mut PositionDialog, clipboard: &mut clipboard::Clipboard) {
+    match clipboard.get_text() {
+        Ok(text) => {
+            dialog.paste(&text);
+            dialog.status.clear();
+        }
+        Err(err) => {
+            warn!("读取剪贴板失败: {err}");
+            dialog.status = format!("读取剪贴板失败: {err}");
+        }
+    }
+}
+
+pub fn position_button_system(
+    mut commands: Commands,
+    fonts: Res<public::asset::Fonts>,
+    mut data: ResMut<Data>,
+    mut dialog: ResMut<PositionDialog>,
+    mut entitys: ResMut<public::EntityResources>,
+    state: Res<State<GameState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut chess_state: ResMut<NextState<ChessState>>,
+    mut game_change: EventWriter<GameChangeEvent>,
+    q_popup: Query<Entity, With<PositionPopup>>,
+    mut clipboard: NonSendMut<clipboard::Clipboard>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &PositionButton),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    let mut pressed = None;
+    for (interaction, mut color, button) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = POSITION_PRESSED_BUTTON_COLOR.into();
+                pressed = Some(*button);
+            }
+            Interaction::Hovered => {
+                *color = POSITION_HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *color = POSITION_NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+    if dialog.submit {
+        dialog.submit = false;
+        pressed = Some(PositionButton::Load);
+    }
+    let pending = *state.get() == GameState::PENDING;
+    match pressed {
+        None => {}
+        Some(PositionButton::Paste) => paste(&mut dialog, &mut clipboard),
+        Some(PositionButton::Copy) => {
+            let fen = data.to_fen();
+            dialog.status = match clipboard.set_text(&fen) {
+                Ok(()) => {
+                    info!("复制局面 {fen}");
+                    String::from("已复制当前局面")
+                }
+                Err(err) => {
+                    warn!("写入剪贴板失败: {err}");
+                    format!("写入剪贴板失败: {err}")
+                }
+            };
+        }
+        Some(PositionButton::Load) => match fen::parse(&dialog.input) {
+            Ok(position) => {
+                commands.remove_resource::<PositionDialog>();
+                if pending {
+                    // 主菜单载入局面后打谱, 弹窗随主菜单销毁
+                    data.mode = Some(GameMode::DeduceGame);
+                    data.set_position(&position);
+                    info!("载入局面打谱 {}", data.start_fen);
+                    game_state.set(GameState::RUNNING);
+                } else {
+                    // 对局中以载入的局面开始新局, 保留对局模式与双方玩家
+                    data.reset();
+                    data.set_position(&position);
+                    info!("载入局面 {}", data.start_fen);
+                    game_change.send(GameChangeEvent(EventAction::Despawn));
+                    game_change.send(GameChangeEvent(EventAction::Spawn));
+                }
+                chess_state.set(data.chess_state());
+            }
+            Err(err) => {
+                info!("FEN错误: {err}");
+                dialog.status = format!("FEN错误: {err}");
+            }
+        },
+        Some(PositionButton::Cancel) => {
+            commands.remove_resource::<PositionDialog>();
+            if pending {
+                commands.entity(entitys.pending_menus.unwrap()).despawn_recursive();
+                entitys.pending_menus =
+                    Some(pending::spawn_main_menu(&mut commands, &data, &fonts));
+            } else {
+                for entity in q_popup.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+        }
+    }
+}
+
+/// 输入内容或提示变化时刷新弹窗
+pub fn render_system(
+    dialog: Res<PositionDialog>,
+    mut input_q: Query<&mut Text, (With<PositionInputText>, Without<PositionStatusText>)>,
+    mut status_q: Query<&mut Text, (With<PositionStatusText>, Without<PositionInputText>)>,
+) {
+    if !dialog.is_changed() {
+        return;
+    }
+    for mut text in input_q.iter_mut() {
+        let section = &mut text.sections[0];
+        if dialog.input.is_empty() {
+            section.value = String::from(POSITION_INPUT_HINT);
+            section.style.color = Color::GRAY;
+        } else {
+            section.value = format!("{}_", dialog.input);
+            section.style.color = Color::ANTIQUE_WHITE;
+        }
+    }
+    for mut text in status_q.iter_mut() {
+        text.sections[0].value = dialog.status.clone();
+    }
+}
+
+/// 重置对局时关闭局面弹窗
+pub fn clear_listen(
+    mut commands: Commands,
+    mut events: EventReader<GameChangeEvent>,
+    q_popup: Query<Entity, With<PositionPopup>>,
+) {
+    for event in events.iter() {
+        if let EventAction::Despawn = event.0 {
+            for entity in q_popup.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            commands.remove_resource::<PositionDialog>();
+        }
+    }
+}
+
+/// 局面弹窗, 主菜单中打开时没有当前局面可复制
+pub fn spawn_popup(
+    commands: &mut Commands,
+    fonts: &public::asset::Fonts,
+    image_handles: &public::asset::Images,
+    copy: bool,
+) -> Entity {
+    let text_style = TextStyle {
+        font: fonts.wenkai.clone(),
+        font_size: 20_f32,
+        color: Color::ANTIQUE_WHITE,
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100_f32),
+                    height: Val::Percent(100_f32),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            PositionPopup,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(ImageBundle {
+                    image: UiImage::new(image_handles.popup.clone()),
+                    style: Style {
+                        width: Val::Px(560_f32),
+                        height: Val::Px(260_f32),
+                        flex_direction: FlexDirection::Column,
+                        justify_content: JustifyContent::SpaceEvenly,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        POSITION_TITLE_TEXT,
+                        TextStyle {
+                            font: fonts.xiaoli.clone(),
+                            font_size: 28_f32,
+                            color: Color::ANTIQUE_WHITE,
+                        },
+                    ));
+                    // 输入框
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Px(500_f32),
+                                min_height: Val::Px(56_f32),
+                                padding: UiRect::all(Val::Px(6_f32)),
+                                ..default()
+                            },
+                            background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            parent.spawn((
+                                TextBundle::from_section(
+                                    POSITION_INPUT_HINT,
+                                    TextStyle {
+                                        color: Color::GRAY,
+                                        ..text_style.clone()
+                                    },
+                                )
+                                .with_style(Style {
+                                    max_width: Val::Px(488_f32),
+                                    ..default()
+                                }),
+                                PositionInputText,
+                            ));
+                        });
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font_size: 18_f32,
+                                color: Color::GOLD,
+                                ..text_style.clone()
+                            },
+                        ),
+                        PositionStatusText,
+                    ));
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Percent(100_f32),
+                                justify_content: JustifyContent::SpaceEvenly,
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            let font = fonts.xiaoli.clone();
+                            make_position_button(
+                                parent,
+                                font.clone(),
+                                POSITION_PASTE_TEXT,
+                                PositionButton::Paste,
+                            );
+                            if copy {
+                                make_position_button(
+                                    parent,
+                                    font.clone(),
+                                    POSITION_COPY_TEXT,
+                                    PositionButton::Copy,
+                                );
+                            }
+                            make_position_button(
+                                parent,
+                                font.clone(),
+                                POSITION_LOAD_TEXT,
+                                PositionButton::Load,
+                            );
+                            make_position_button(
+                                parent,
+                                font,
+                                POSITION_CANCEL_TEXT,
+                                PositionButton::Cancel,
+                            );
+                        });
+                });
+        })
+        .id()
+}
+
+fn make_position_button(
+    parent: &mut ChildBuilder,
+    font: Handle<Font>,
+    text: &str,
+    button: PositionButton,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(110_f32),
+                    height: Val::Px(40_f32),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: POSITION_NORMAL_BUTTON_COLOR.into(),
+                ..default()
+            },
+            button,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                text,
+                TextStyle {
+                    font,
+                    font_size: 24_f32,
+                    color: Color::ANTIQUE_WHITE,
+                },
+            ));
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paste() {
+        let mut dialog = PositionDialog::default();
+        dialog.paste(
+            "  rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR\r\n w  - -\t0 1\n",
+        );
+        assert_eq!(dialog.input, public::START_POS);
+        assert!(fen::parse(&dialog.input).is_ok());
+
+        dialog.paste(&"9/".repeat(100));
+        assert_eq!(dialog.input.chars().count(), POSITION_FEN_MAX_LEN);
+    }
+}
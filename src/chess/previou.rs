@@ -1,5 +1,6 @@
 use bevy::prelude::*;
 
+use crate::game::Data;
 use crate::public::{get_piece_render_percent, Pos};
 
 #[derive(Component)]
@@ -16,6 +17,7 @@ pub struct PiecePreviouMove(pub Pos, pub Pos);
 
 pub fn piece_previou_move(
     mut commands: Commands,
+    data: Res<Data>,
     mut params: ParamSet<(
         Query<(&mut Transform, &mut Visibility), With<PiecePreviouStart>>,
         Query<(&mut Transform, &mut Visibility), With<PiecePreviouEnd>>,
@@ -27,13 +29,15 @@ pub fn piece_previou_move(
 
         for (mut tf, mut vis) in params.p0().iter_mut() {
             *vis = Visibility::Hidden;
-            (tf.translation.x, tf.translation.y) = get_piece_render_percent(src.row, src.col);
+            (tf.translation.x, tf.translation.y) =
+                get_piece_render_percent(src.row, src.col, data.flipped);
             *vis = Visibility::Inherited;
         }
 
         for (mut tf, mut vis) in params.p1().iter_mut() {
             *vis = Visibility::Hidden;
-            (tf.translation.x, tf.translation.y) = get_piece_render_percent(dst.row, dst.col);
+            (tf.translation.x, tf.translation.y) =
+                get_piece_render_percent(dst.row, dst.col, data.flipped);
             *vis = Visibility::Inherited;
         }
 
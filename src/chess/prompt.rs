@@ -0,0 +1,144 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use bevy::tasks::Task;
+use futures_lite::future;
+
+use crate::{
+    event::{BroadRefreshEvent, EventAction, GameChangeEvent, PromptEvent, SwithPlayerEvent},
+    game::{Data, GameMode},
+    player,
+    public::{self, get_piece_render_percent, BroadEntitys, Pos},
+};
+
+// 提示箭头颜色
+pub const PROMPT_ARROW_COLOR: Color = Color::rgba(0.2, 0.8, 0.3, 0.75);
+// 提示箭头宽度
+pub const PROMPT_ARROW_WIDTH: f32 = 8_f32;
+// 提示箭头的箭头长度
+pub const PROMPT_ARROW_HEAD: f32 = 28_f32;
+
+/// 提示搜索任务
+#[derive(Component)]
+pub struct PromptSearch(Task<String>);
+
+/// 提示箭头
+#[derive(Component)]
+pub struct PromptArrow;
+
+pub fn event_listen(
+    mut commands: Commands,
+    mut events: EventReader<PromptEvent>,
+    mut data: ResMut<Data>,
+    q_search: Query<Entity, With<PromptSearch>>,
+    q_arrow: Query<Entity, With<PromptArrow>>,
+) {
+    for _ in events.iter() {
+        // 正在提示中
+        if !q_search.is_empty() || !q_arrow.is_empty() {
+            return;
+        }
+        data.prompt_num += 1;
+        info!("提示 第{}次 {}", data.prompt_num, data.engine.to_fen());
+        let task = super::ai_chess::spawn_search(data.engine.to_fen(), player::AiLevel::Master);
+        commands.spawn(PromptSearch(task));
+        return;
+    }
+}
+
+/// 轮询提示结果, 在棋盘上绘制从起点到终点的箭头
+pub fn prompt_result(
+    mut commands: Commands,
+    data: Res<Data>,
+    entitys: Res<BroadEntitys>,
+    sound_handles: Res<public::asset::Sounds>,
+    mut q_search: Query<(Entity, &mut PromptSearch)>,
+) {
+    for (entity, mut search) in q_search.iter_mut() {
+        let iccs = match future::block_on(future::poll_once(&mut search.0)) {
+            Some(iccs) => iccs,
+            None => continue,
+        };
+        commands.entity(entity).despawn();
+        let broad = match entitys.broad {
+            Some(broad) => broad,
+            None => continue,
+        };
+        let ((src_row, src_col), (dst_row, dst_col)) = data.parse_route(iccs);
+        info!("提示走法 {src_row}-{src_col} {dst_row}-{dst_col}");
+        commands.entity(broad).with_children(|parent| {
+            spawn_arrow(
+                parent,
+                Pos::new(src_row, src_col),
+                Pos::new(dst_row, dst_col),
+                data.flipped,
+            );
+        });
+        commands.spawn(super::audio::play_once(sound_handles.select.clone()));
+    }
+}
+
+/// 行棋、悔棋或重置对局后清除提示, 打谱分析时重新搜索当前局面
+pub fn clear_listen(
+    mut commands: Commands,
+    data: Res<Data>,
+    mut swith_events: EventReader<SwithPlayerEvent>,
+    mut refresh_events: EventReader<BroadRefreshEvent>,
+    mut change_events: EventReader<GameChangeEvent>,
+    q_search: Query<Entity, With<PromptSearch>>,
+    q_arrow: Query<Entity, With<PromptArrow>>,
+) {
+    let despawn = change_events.iter().filter(|e| matches!(e.0, EventAction::Despawn)).count();
+    if swith_events.iter().count() + refresh_events.iter().count() + despawn == 0 {
+        return;
+    }
+    for entity in q_search.iter().chain(q_arrow.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+    if despawn == 0 && data.mode == Some(GameMode::DeduceGame) && data.analysis {
+        let task = super::ai_chess::spawn_search(data.engine.to_fen(), player::AiLevel::Master);
+        commands.spawn(PromptSearch(task));
+    }
+}
+
+fn spawn_arrow(parent: &mut ChildBuilder, src: Pos, dst: Pos, flipped: bool) {
+    let start = Vec2::from(get_piece_render_percent(src.row, src.col, flipped));
+    let end = Vec2::from(get_piece_render_percent(dst.row, dst.col, flipped));
+    let angle = (end - start).y.atan2((end - start).x);
+    let length = (end - start).length();
+
+    // 箭身
+    parent.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: PROMPT_ARROW_COLOR,
+                custom_size: Some(Vec2::new(length, PROMPT_ARROW_WIDTH)),
+                ..default()
+            },
+            transform: Transform::from_translation(((start + end) / 2_f32).extend(1.2_f32))
+                .with_rotation(Quat::from_rotation_z(angle)),
+            ..default()
+        },
+        PromptArrow,
+    ));
+
+    // 箭头: 从终点向后张开的两条短线
+    for offset in [PI / 6_f32, -PI / 6_f32] {
+        let head_angle = angle + PI + offset;
+        let center =
+            end + Vec2::new(head_angle.cos(), head_angle.sin()) * PROMPT_ARROW_HEAD / 2_f32;
+        parent.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: PROMPT_ARROW_COLOR,
+                    custom_size: Some(Vec2::new(PROMPT_ARROW_HEAD, PROMPT_ARROW_WIDTH)),
+                    ..default()
+                },
+                transform: Transform::from_translation(center.extend(1.2_f32))
+                    .with_rotation(Quat::from_rotation_z(head_angle)),
+                ..default()
+            },
+            PromptArrow,
+        ));
+    }
+}
@@ -0,0 +1,365 @@
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use chessai::position::pos2iccs;
+
+use crate::{
+    component::piece::Side,
+    event::BroadRefreshEvent,
+    fen,
+    game::Data,
+    notation::Notation,
+    player::Record,
+    public::{self, BroadEntitys, Pos},
+};
+
+use super::info::PlayerInfoPanel;
+
+pub const RECORD_TITLE_TEXT: &str = "棋谱";
+// 显示的回合数, 更早的回合滚轮翻看
+pub const RECORD_VISIBLE_ROWS: usize = 8;
+
+pub const RECORD_NORMAL_BUTTON_COLOR: Color = Color::NONE;
+pub const RECORD_HOVERED_BUTTON_COLOR: Color = Color::rgb(0.30, 0.30, 0.30);
+
+/// 棋谱标题, 显示记法与回看的步数, 点击切换记法
+#[derive(Component)]
+pub struct RecordTitle;
+
+/// 棋谱列表, 记录已显示的走法与向上翻看的回合数
+#[derive(Component, Default)]
+pub struct RecordList {
+    scroll: usize,
+    shown: Option<(usize, Option<(Pos, Pos)>, Option<usize>, Notation)>,
+}
+
+/// 由行棋记录生成全部棋谱
+pub fn records(data: &Data) -> Vec<Record> {
+    data.history
+        .iter()
+        .enumerate()
+        .map(|(i, step)| {
+            let code = pos2iccs(step.src.row, step.src.col, step.dst.row, step.dst.col);
+            let value = data
+                .notation
+                .encode(&data.position_at(i), step.src, step.dst)
+                .unwrap_or_else(|_| code.clone());
+            Record {
+                serial: i + 1,
+                code,
+                value,
+            }
+        })
+        .collect()
+}
+
+/// 按开局局面的行棋方与回合数分行: (回合数, 红方走法, 黑方走法), 黑方先行时第一行红方为空
+pub fn rows(data: &Data) -> Vec<(usize, Option<Record>, Option<Record>)> {
+    let start = fen::parse(&data.start_fen).ok();
+    let round = start.map_or(1, |position| position.fullmove());
+    let mut records = records(data).into_iter();
+    let mut rows = Vec::new();
+    if start.is_some_and(|position| position.side == Side::Black) {
+        if let Some(black) = records.next() {
+            rows.push((round, None, Some(black)));
+        }
+    }
+    while let Some(red) = records.next() {
+        rows.push((round + rows.len(), Some(red), records.next()));
+    }
+    rows
+}
+
+/// 棋谱面板, 位于上方玩家信息框下方, 随棋盘翻转左右交换
+pub fn spawn_panel(parent: &mut ChildBuilder, on_left: bool, fonts: &public::asset::Fonts) {
+    let mut style = Style {
+        position_type: PositionType::Absolute,
+        top: Val::Percent(58_f32),
+        width: Val::Px(200_f32),
+        height: Val::Px(250_f32),
+        flex_direction: FlexDirection::Column,
+        padding: UiRect::all(Val::Px(4_f32)),
+        ..default()
+    };
+    if on_left {
+        style.left = Val::Percent(10_f32);
+    } else {
+        style.right = Val::Percent(10_f32);
+    }
+
+    parent
+        .spawn((
+            NodeBundle {
+                style,
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.35).into(),
+                ..default()
+            },
+            PlayerInfoPanel,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            margin: UiRect::bottom(Val::Px(4_f32)),
+                            ..default()
+                        },
+                        background_color: RECORD_NORMAL_BUTTON_COLOR.into(),
+                        ..default()
+                    },
+                    RecordTitle,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        RECORD_TITLE_TEXT,
+                        TextStyle {
+                            font: fonts.xiaoli.clone(),
+                            font_size: 20_f32,
+                            color: Color::ANTIQUE_WHITE,
+                        },
+                    ));
+                });
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100_f32),
+                        flex_grow: 1_f32,
+                        flex_direction: FlexDirection::Column,
+                        overflow: Overflow::clip(),
+                        ..default()
+                    },
+                    ..default()
+                },
+                Interaction::default(),
+                RecordList::default(),
+            ));
+        });
+}
+
+fn make_record_button(parent: &mut ChildBuilder, font: Handle<Font>, record: Record, color: Color) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(76_f32),
+                    padding: UiRect::horizontal(Val::Px(2_f32)),
+                    ..default()
+                },
+                background_color: RECORD_NORMAL_BUTTON_COLOR.into(),
+                // 悬停在走法上时仍可滚轮翻看
+                focus_policy: FocusPolicy::Pass,
+                ..default()
+            },
+            record.clone(),
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    record.value,
+                    TextStyle {
+                        font,
+                        font_size: 18_f32,
+                        color,
+                    },
+                )
+                .with_no_wrap(),
+            );
+        });
+}
+
+/// 走法变化或回看时重建棋谱列表, 每回合一行
+pub fn record_system(
+    mut commands: Commands,
+    data: Res<Data>,
+    fonts: Res<public::asset::Fonts>,
+    mut list_q: Query<(Entity, &mut RecordList)>,
+    title_q: Query<&Children, With<RecordTitle>>,
+    mut text_q: Query<&mut Text>,
+) {
+    let shown = Some((data.history.len(), data.get_last_move(), data.preview, data.notation));
+    for (entity, mut list) in list_q.iter_mut() {
+        if list.shown == shown && !list.is_changed() {
+            continue;
+        }
+        // 只有翻看时才需要检测列表的变化
+        let list = list.bypass_change_detection();
+        list.shown = shown;
+
+        let rows = rows(&data);
+        let max = rows.len().saturating_sub(RECORD_VISIBLE_ROWS);
+        list.scroll = list.scroll.min(max);
+        let end = rows.len() - list.scroll;
+        commands.entity(entity).despawn_descendants();
+        commands.entity(entity).with_children(|parent| {
+            for (round, red, black) in rows[end.saturating_sub(RECORD_VISIBLE_ROWS)..end].iter() {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            height: Val::Px(24_f32),
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(
+                            TextBundle::from_section(
+                                format!("{round}."),
+                                TextStyle {
+                                    font: fonts.wenkai.clone(),
+                                    font_size: 16_f32,
+                                    color: Color::GRAY,
+                                },
+                            )
+                            .with_style(Style {
+                                width: Val::Px(32_f32),
+                                ..default()
+                            }),
+                        );
+                        if red.is_none() {
+                            // 黑方先行, 红方一栏留空
+                            parent.spawn(NodeBundle {
+                                style: Style {
+                                    width: Val::Px(76_f32),
+                                    ..default()
+                                },
+                                ..default()
+                            });
+                        }
+                        for record in red.iter().chain(black.iter()) {
+                            let color = match data.preview {
+                                Some(serial) if serial == record.serial => Color::GOLD,
+                                _ => Color::ANTIQUE_WHITE,
+                            };
+                            make_record_button(parent, fonts.wenkai.clone(), record.clone(), color);
+                        }
+                    });
+            }
+        });
+    }
+
+    let value = match data.preview {
+        Some(serial) => format!("{RECORD_TITLE_TEXT} 回看第{serial}步"),
+        None => format!("{RECORD_TITLE_TEXT} {}", data.notation.name()),
+    };
+    for children in title_q.iter() {
+        let mut text = text_q.get_mut(children[0]).unwrap();
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+/// 点击棋谱标题切换显示的记法
+pub fn notation_system(
+    mut data: ResMut<Data>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<RecordTitle>),
+    >,
+) {
+    for (interaction, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                data.notation = data.notation.next();
+                info!("棋谱记法 {}", data.notation.name());
+            }
+            Interaction::Hovered => {
+                *color = RECORD_HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *color = RECORD_NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+/// 鼠标悬停在棋谱上时滚轮翻看
+pub fn scroll_system(
+    mut wheel: EventReader<MouseWheel>,
+    mut list_q: Query<(&Interaction, &mut RecordList)>,
+) {
+    for event in wheel.iter() {
+        for (interaction, mut list) in list_q.iter_mut() {
+            if *interaction != Interaction::None && event.y != 0_f32 {
+                // 超出范围时在重建列表时修正
+                list.scroll = match event.y > 0_f32 {
+                    true => list.scroll + 1,
+                    false => list.scroll.saturating_sub(1),
+                };
+            }
+        }
+    }
+}
+
+/// 点击棋谱回看该步后的局面, 再次点击或点击最后一步回到当前局面
+pub fn preview_system(
+    mut data: ResMut<Data>,
+    mut entitys: ResMut<BroadEntitys>,
+    mut refresh: EventWriter<BroadRefreshEvent>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &Record),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut color, record) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                data.preview =
+                    if data.preview == Some(record.serial) || record.serial == data.history.len() {
+                        None
+                    } else {
+                        Some(record.serial)
+                    };
+                info!("回看 {:?} {} {}", data.preview, record.code, record.value);
+                // 取消选择, 选中的棋子会在刷新棋盘时一并销毁
+                data.selected = None;
+                entitys.selected = None;
+                refresh.send(BroadRefreshEvent);
+            }
+            Interaction::Hovered => {
+                *color = RECORD_HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *color = RECORD_NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serials(data: &Data) -> Vec<(usize, Option<usize>, Option<usize>)> {
+        rows(data)
+            .into_iter()
+            .map(|(round, red, black)| {
+                (round, red.map(|record| record.serial), black.map(|record| record.serial))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rows() {
+        let mut data = Data::new();
+        data.load_fen(public::START_POS).unwrap();
+        data.try_move(Pos::new(2, 7), Pos::new(2, 4));
+        data.try_move(Pos::new(9, 7), Pos::new(7, 6));
+        data.try_move(Pos::new(0, 7), Pos::new(2, 6));
+        assert_eq!(serials(&data), [(1, Some(1), Some(2)), (2, Some(3), None)]);
+    }
+
+    #[test]
+    fn test_rows_black_first() {
+        // 黑方先行, 第一行只有黑方走法, 回合数接FEN
+        let mut data = Data::new();
+        data.load_fen("4k4/9/9/9/9/9/9/9/4A4/3AK4 b - - 0 12").unwrap();
+        assert!(serials(&data).is_empty());
+        assert!(data.try_move(Pos::new(9, 4), Pos::new(9, 3)));
+        assert_eq!(serials(&data), [(12, None, Some(1))]);
+        assert!(data.try_move(Pos::new(1, 4), Pos::new(2, 3)));
+        assert!(data.try_move(Pos::new(9, 3), Pos::new(8, 3)));
+        assert_eq!(serials(&data), [(12, None, Some(1)), (13, Some(2), Some(3))]);
+    }
+}
@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+
+use crate::{
+    event::{BroadRefreshEvent, RetractEvent},
+    game::{Data, GameMode},
+    player,
+    public::{self, BroadEntitys},
+    status::ChessState,
+};
+
+use super::info::{self, PlayerInfoAction};
+
+pub fn event_listen(
+    mut commands: Commands,
+    mut events: EventReader<RetractEvent>,
+    mut data: ResMut<Data>,
+    mut entitys: ResMut<BroadEntitys>,
+    mut chess_state: ResMut<NextState<ChessState>>,
+    mut refresh: EventWriter<BroadRefreshEvent>,
+    mut action_q: Query<(&player::Player, &mut Text), With<PlayerInfoAction>>,
+    sound_handles: Res<public::asset::Sounds>,
+) {
+    for _ in events.iter() {
+        // 人机对弈轮到玩家时需连同AI的应着一起撤销, AI先行且只走了一步时不能悔棋
+        let steps = match data.mode {
+            Some(GameMode::AiGame) if data.current_side != data.ai_side => 2,
+            _ => 1,
+        };
+        if data.history.len() < steps {
+            info!("没有可以悔的棋");
+            commands.spawn(super::audio::play_once(sound_handles.invalid.clone()));
+            return;
+        }
+
+        // 取消选择, 选中的棋子会在刷新棋盘时一并销毁
+        data.selected = None;
+        entitys.selected = None;
+
+        for _ in 0..steps {
+            data.undo_move();
+        }
+        info!("悔棋 round:{} side:{:?}", data.round, data.current_side);
+        data.get_current_player().reset_current_timer();
+
+        chess_state.set(data.chess_state());
+        refresh.send(BroadRefreshEvent);
+
+        // 刷新双方行动信息
+        info::update_action_text(data.current_side.unwrap(), &mut action_q);
+        commands.spawn(super::audio::play_once(sound_handles.go.clone()));
+        return;
+    }
+}
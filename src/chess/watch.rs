@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+
+use crate::{
+    component::piece::Side,
+    event::{BroadRefreshEvent, GameoverEvent, SwithPlayerEvent},
+    game::{Data, GameMode},
+    net::{Message, NetConnection, NetEvent},
+    player,
+    public::{self, BroadEntitys, Pos},
+    status::ChessState,
+};
+
+use super::{
+    info::{self, PlayerInfoAction},
+    previou::PiecePreviouMove,
+};
+
+/// 观战: 主场方同步的走法与实时走法, 只显示不能操作
+pub fn receive_system(
+    mut commands: Commands,
+    mut data: ResMut<Data>,
+    mut entitys: ResMut<BroadEntitys>,
+    connection: Res<NetConnection>,
+    state: Res<State<ChessState>>,
+    mut chess_state: ResMut<NextState<ChessState>>,
+    mut refresh: EventWriter<BroadRefreshEvent>,
+    mut gameover: EventWriter<GameoverEvent>,
+    mut swith_player: EventWriter<SwithPlayerEvent>,
+    sound_handles: Res<public::asset::Sounds>,
+    mut action_q: Query<(&player::Player, &mut Text), With<PlayerInfoAction>>,
+) {
+    if data.mode != Some(GameMode::WatchGame) {
+        return;
+    }
+    while let Some(event) = connection.poll() {
+        let message = match event {
+            NetEvent::Message(message) => message,
+            NetEvent::Disconnected => {
+                info!("与主场方断开, 等待重连");
+                continue;
+            }
+            _ => continue,
+        };
+        match message {
+            Message::Sync(moves, _) => {
+                // 从开局重走全部走法
+                info!("观战同步 {} 步", moves.len());
+                data.reset();
+                for iccs in moves {
+                    let ((src_row, src_col), (dst_row, dst_col)) = data.parse_route(iccs.clone());
+                    if !data.try_move(Pos::new(src_row, src_col), Pos::new(dst_row, dst_col)) {
+                        warn!("同步走法不合法 {iccs}");
+                        break;
+                    }
+                }
+                data.selected = None;
+                entitys.selected = None;
+                refresh.send(BroadRefreshEvent);
+                if let Some((src, dst)) = data.get_last_move() {
+                    commands.spawn(PiecePreviouMove(src, dst));
+                }
+                info::update_action_text(data.current_side.unwrap(), &mut action_q);
+                if *state.get() != ChessState::Gameover {
+                    chess_state.set(data.chess_state());
+                }
+            }
+            Message::Move(seq, iccs) => {
+                let ((src_row, src_col), (dst_row, dst_col)) = data.parse_route(iccs.clone());
+                let (src, dst) = (Pos::new(src_row, src_col), Pos::new(dst_row, dst_col));
+                if *state.get() == ChessState::Gameover
+                    || seq != data.history.len() + 1
+                    || !data.can_move(src, dst)
+                {
+                    warn!("观战走法不合法 {seq} {iccs}");
+                    continue;
+                }
+                data.make_move(src, dst);
+                refresh.send(BroadRefreshEvent);
+                super::chess::finish_move(
+                    &mut data,
+                    &mut commands,
+                    &sound_handles,
+                    &mut gameover,
+                    &mut swith_player,
+                );
+                // 换边后再处理下一步
+                return;
+            }
+            Message::Over(winner) if *state.get() != ChessState::Gameover => {
+                gameover.send(GameoverEvent(match winner {
+                    Some(Side::White) => chessai::pregen::Winner::White,
+                    Some(Side::Black) => chessai::pregen::Winner::Black,
+                    None => chessai::pregen::Winner::Tie,
+                }));
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,25 @@
+//! 系统剪贴板的文本读写
+
+/// 系统剪贴板, 在应用生命周期内保持; Linux下复制的文本由持有者提供, 提前释放会丢失
+#[derive(Default)]
+pub struct Clipboard(Option<arboard::Clipboard>);
+
+impl Clipboard {
+    /// 首次使用时连接剪贴板, 失败时下次再试
+    fn inner(&mut self) -> Result<&mut arboard::Clipboard, String> {
+        if self.0.is_none() {
+            self.0 = Some(arboard::Clipboard::new().map_err(|err| err.to_string())?);
+        }
+        Ok(self.0.as_mut().unwrap())
+    }
+
+    /// 读取剪贴板中的文本
+    pub fn get_text(&mut self) -> Result<String, String> {
+        self.inner()?.get_text().map_err(|err| err.to_string())
+    }
+
+    /// 写入文本到剪贴板
+    pub fn set_text(&mut self, text: &str) -> Result<(), String> {
+        self.inner()?.set_text(text).map_err(|err| err.to_string())
+    }
+}
@@ -106,9 +106,25 @@ impl Side {
             Self::Black => "黑",
         }
     }
+
+    /// 对方
+    pub fn opponent(&self) -> Side {
+        match self {
+            Self::White => Self::Black,
+            Self::Black => Self::White,
+        }
+    }
+
+    /// 本方获胜
+    pub fn winner(&self) -> chessai::pregen::Winner {
+        match self {
+            Self::White => chessai::pregen::Winner::White,
+            Self::Black => chessai::pregen::Winner::Black,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Kind {
     // 車
     Rook,
@@ -130,7 +146,7 @@ impl Kind {
     pub fn code(&self) -> &str {
         match self {
             Kind::Rook => "r",
-            Kind::Knight => "k",
+            Kind::Knight => "n",
             Kind::Bishop => "b",
             Kind::Advisor => "a",
             Kind::Cannon => "c",
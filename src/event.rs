@@ -15,3 +15,30 @@ pub struct SwithPlayerEvent;
 
 #[derive(Event)]
 pub struct GameoverEvent(pub chessai::pregen::Winner);
+
+#[derive(Event)]
+pub struct RetractEvent;
+
+#[derive(Event)]
+pub struct BroadRefreshEvent;
+
+#[derive(Event)]
+pub struct PromptEvent;
+
+#[derive(Event)]
+pub struct PeaceEvent;
+
+#[derive(Event)]
+pub struct PositionEvent;
+
+pub enum DeduceAction {
+    // 后退一步
+    Back,
+    // 前进一步
+    Forward,
+    // 开关引擎分析
+    Analysis,
+}
+
+#[derive(Event)]
+pub struct DeduceEvent(pub DeduceAction);
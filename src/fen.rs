@@ -0,0 +1,364 @@
+//! FEN局面的解析与生成
+//!
+//! 棋盘从黑方底线(第9行)写起, 红方大写、黑方小写, 马记为n(兼容h), 相记为b(兼容e)。
+//! 其后依次为行棋方、两个占位的"-"、未吃子的半回合数与回合数
+
+use std::fmt;
+
+use crate::component::piece::{Kind, Piece, Side};
+use crate::notation::BroadMap;
+
+/// 各兵种一方的最大数量
+const MAX_PIECES: [(Kind, usize); 7] = [
+    (Kind::Rook, 2),
+    (Kind::Knight, 2),
+    (Kind::Bishop, 2),
+    (Kind::Advisor, 2),
+    (Kind::Cannon, 2),
+    (Kind::Pawn, 5),
+    (Kind::King, 1),
+];
+
+/// 解析错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    // 空字符串
+    Empty,
+    // 棋盘不是10行
+    RankCount(usize),
+    // 从上往下第几行不是9列
+    RankWidth(usize),
+    // 无法识别的棋子字母
+    Piece(char),
+    // 行棋方不是w/r/b
+    Side(String),
+    // 步数不是数字
+    Number(String),
+    // 一方帅(将)的数量不是1
+    King(Side, usize),
+    // 一方某个兵种超出数量
+    TooMany(Side, Kind, usize),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::Empty => write!(f, "FEN为空"),
+            FenError::RankCount(count) => write!(f, "棋盘应为10行, 实际{count}行"),
+            FenError::RankWidth(rank) => write!(f, "第{rank}行不是9列"),
+            FenError::Piece(c) => write!(f, "无法识别的棋子: {c}"),
+            FenError::Side(text) => write!(f, "无法识别的行棋方: {text}"),
+            FenError::Number(text) => write!(f, "步数不是数字: {text}"),
+            FenError::King(side, 0) => write!(f, "{}方没有帅(将)", side.name()),
+            FenError::King(side, count) => write!(f, "{}方有{count}个帅(将)", side.name()),
+            FenError::TooMany(side, kind, count) => {
+                write!(f, "{}方有{count}个{}", side.name(), kind.name())
+            }
+        }
+    }
+}
+
+/// FEN描述的局面, 回合数与对局数据一致: 红方行棋后加一
+#[derive(Debug, Clone, Copy)]
+pub struct Position {
+    // 棋盘地图
+    pub broad_map: BroadMap,
+    // 行棋方
+    pub side: Side,
+    // 没有吃子的步数
+    pub noeat_move_num: usize,
+    // 当前回合数
+    pub round: usize,
+}
+
+impl Position {
+    /// 开局局面: 第一回合, 未吃子步数为0
+    pub fn new(broad_map: BroadMap, side: Side) -> Self {
+        let round = match side {
+            Side::White => 0,
+            Side::Black => 1,
+        };
+        Self {
+            broad_map,
+            side,
+            noeat_move_num: 0,
+            round,
+        }
+    }
+
+    /// FEN中的回合数, 从1开始, 黑方行棋后加一
+    pub fn fullmove(&self) -> usize {
+        match self.side {
+            Side::White => self.round + 1,
+            Side::Black => self.round.max(1),
+        }
+    }
+}
+
+fn parse_piece(c: char, row: usize, col: usize) -> Result<Piece, FenError> {
+    let kind = match c.to_ascii_lowercase() {
+        'r' => Kind::Rook,
+        'n' | 'h' => Kind::Knight,
+        'b' | 'e' => Kind::Bishop,
+        'a' => Kind::Advisor,
+        'c' => Kind::Cannon,
+        'p' => Kind::Pawn,
+        'k' => Kind::King,
+        _ => return Err(FenError::Piece(c)),
+    };
+    Ok(match c.is_ascii_uppercase() {
+        true => Piece::white(kind, row, col),
+        false => Piece::black(kind, row, col),
+    })
+}
+
+fn parse_number(text: Option<&str>, default: usize) -> Result<usize, FenError> {
+    match text {
+        None => Ok(default),
+        Some(text) => text.parse().map_err(|_| FenError::Number(text.to_string())),
+    }
+}
+
+/// 解析FEN, 省略的字段取开局默认值
+pub fn parse(fen: &str) -> Result<Position, FenError> {
+    let mut fields = fen.split_whitespace();
+    let ranks: Vec<&str> = fields.next().ok_or(FenError::Empty)?.split('/').collect();
+    if ranks.len() != 10 {
+        return Err(FenError::RankCount(ranks.len()));
+    }
+    let mut broad_map: BroadMap = Default::default();
+    for (i, rank) in ranks.iter().enumerate() {
+        let row = 9 - i;
+        let mut col = 0;
+        for c in rank.chars() {
+            if let Some(n) = c.to_digit(10) {
+                col += n as usize;
+                continue;
+            }
+            let piece = parse_piece(c, row, col)?;
+            if col > 8 {
+                return Err(FenError::RankWidth(i + 1));
+            }
+            broad_map[row][col] = Some(piece);
+            col += 1;
+        }
+        if col != 9 {
+            return Err(FenError::RankWidth(i + 1));
+        }
+    }
+    validate(&broad_map)?;
+
+    let side = match fields.next() {
+        None | Some("w") | Some("r") => Side::White,
+        Some("b") => Side::Black,
+        Some(text) => return Err(FenError::Side(text.to_string())),
+    };
+    // 中国象棋没有易位与吃过路兵, 两个字段忽略
+    let mut fields = fields.skip(2);
+    let noeat_move_num = parse_number(fields.next(), 0)?;
+    let fullmove = parse_number(fields.next(), 1)?;
+    let round = match side {
+        Side::White => fullmove.saturating_sub(1),
+        Side::Black => fullmove.max(1),
+    };
+    Ok(Position {
+        broad_map,
+        side,
+        noeat_move_num,
+        round,
+    })
+}
+
+/// 生成FEN
+pub fn serialize(position: &Position) -> String {
+    let mut ranks = Vec::with_capacity(10);
+    for pieces in position.broad_map.iter().rev() {
+        let mut rank = String::new();
+        let mut num = 0;
+        for piece in pieces {
+            match piece {
+                Some(piece) => {
+                    if num > 0 {
+                        rank.push_str(&num.to_string());
+                        num = 0;
+                    }
+                    rank.push_str(&piece.code());
+                }
+                None => num += 1,
+            }
+        }
+        if num > 0 {
+            rank.push_str(&num.to_string());
+        }
+        ranks.push(rank);
+    }
+    format!(
+        "{} {} - - {} {}",
+        ranks.join("/"),
+        position.side.code(),
+        position.noeat_move_num,
+        position.fullmove()
+    )
+}
+
+/// 检查双方子力数量: 各有一个帅(将), 其余兵种不超过开局数量
+pub fn validate(broad_map: &BroadMap) -> Result<(), FenError> {
+    for side in [Side::White, Side::Black] {
+        for (kind, max) in MAX_PIECES {
+            let count = broad_map
+                .iter()
+                .flatten()
+                .flatten()
+                .filter(|piece| piece.side == side && piece.kind == kind)
+                .count();
+            if kind == Kind::King && count != 1 {
+                return Err(FenError::King(side, count));
+            }
+            if count > max {
+                return Err(FenError::TooMany(side, kind, count));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 测试用的随机局面
+#[cfg(test)]
+pub mod testing {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    /// 按种子生成随机局面: 帅(将)在九宫内, 其余棋子随机放在空位上
+    pub fn random_position(seed: u64) -> Position {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut broad_map: BroadMap = Default::default();
+        broad_map[rng.gen_range(0..3)][rng.gen_range(3..6)] = Some(Piece::white(Kind::King, 0, 0));
+        broad_map[rng.gen_range(7..10)][rng.gen_range(3..6)] = Some(Piece::black(Kind::King, 0, 0));
+        for side in [Side::White, Side::Black] {
+            for (kind, max) in MAX_PIECES.iter().filter(|(kind, _)| *kind != Kind::King) {
+                for _ in 0..rng.gen_range(0..=*max) {
+                    loop {
+                        let (row, col) = (rng.gen_range(0..10), rng.gen_range(0..9));
+                        if broad_map[row][col].is_none() {
+                            broad_map[row][col] = Some(Piece {
+                                side,
+                                kind: *kind,
+                                row,
+                                col,
+                            });
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        // 修正帅(将)记录的坐标
+        for (row, pieces) in broad_map.iter_mut().enumerate() {
+            for (col, piece) in pieces.iter_mut().enumerate() {
+                if let Some(piece) = piece {
+                    (piece.row, piece.col) = (row, col);
+                }
+            }
+        }
+        let side = match rng.gen_bool(0.5) {
+            true => Side::White,
+            false => Side::Black,
+        };
+        Position {
+            broad_map,
+            side,
+            noeat_move_num: rng.gen_range(0..120),
+            round: rng.gen_range(1..300),
+        }
+    }
+
+    /// 棋盘上每个位置的棋子与其记录的坐标
+    pub fn squares(broad_map: &BroadMap) -> Vec<Option<(Side, Kind, usize, usize)>> {
+        broad_map
+            .iter()
+            .flatten()
+            .map(|piece| piece.map(|piece| (piece.side, piece.kind, piece.row, piece.col)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::*;
+    use super::*;
+    use crate::public::START_POS;
+
+    #[test]
+    fn test_start_pos() {
+        let position = parse(START_POS).unwrap();
+        assert_eq!(position.side, Side::White);
+        assert_eq!((position.noeat_move_num, position.round), (0, 0));
+        let king = position.broad_map[0][4].unwrap();
+        assert_eq!((king.side, king.kind), (Side::White, Kind::King));
+        let knight = position.broad_map[9][1].unwrap();
+        assert_eq!(
+            (knight.side, knight.kind, knight.row, knight.col),
+            (Side::Black, Kind::Knight, 9, 1)
+        );
+        assert_eq!(position.broad_map.iter().flatten().flatten().count(), 32);
+        assert_eq!(serialize(&position), START_POS);
+    }
+
+    #[test]
+    fn test_parse() {
+        // 兼容h/e字母、r表示红方与省略的字段
+        let position = parse("4k4/9/9/9/9/9/9/9/4A4/2H1KE3 r").unwrap();
+        assert_eq!(position.side, Side::White);
+        assert_eq!(position.broad_map[0][2].unwrap().kind, Kind::Knight);
+        assert_eq!(position.broad_map[0][5].unwrap().kind, Kind::Bishop);
+        assert_eq!(serialize(&position), "4k4/9/9/9/9/9/9/9/4A4/2N1KB3 w - - 0 1");
+
+        // 黑方行棋时回合数与FEN一致
+        let position = parse("3k5/9/9/9/9/9/9/9/9/4K4 b - - 3 12").unwrap();
+        assert_eq!((position.side, position.noeat_move_num, position.round), (Side::Black, 3, 12));
+        assert_eq!(position.fullmove(), 12);
+        let position = parse("3k5/9/9/9/9/9/9/9/9/4K4 w - - 3 12").unwrap();
+        assert_eq!((position.round, position.fullmove()), (11, 12));
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            ("", FenError::Empty),
+            ("4k4/9/9/9/9/9/9/4A4/4K4 w", FenError::RankCount(9)),
+            ("4k4/9/9/9/9/9/9/9/9/4K4/9 w", FenError::RankCount(11)),
+            ("4k4/8/9/9/9/9/9/9/9/4K4 w", FenError::RankWidth(2)),
+            ("4k4/9/9/9/9/9/9/9/9/4K5 w", FenError::RankWidth(10)),
+            ("4k4/9/9/9/9/9/9/9/9/9K w", FenError::RankWidth(10)),
+            ("4k4/9/9/4x4/9/9/9/9/9/4K4 w", FenError::Piece('x')),
+            ("4k4/9/9/9/9/9/9/9/9/4K4 x", FenError::Side(String::from("x"))),
+            ("4k4/9/9/9/9/9/9/9/9/4K4 w - - a 1", FenError::Number(String::from("a"))),
+            ("4k4/9/9/9/9/9/9/9/9/4K4 w - - 0 -1", FenError::Number(String::from("-1"))),
+            ("4k4/9/9/9/9/9/9/9/9/9 w", FenError::King(Side::White, 0)),
+            ("3kk4/9/9/9/9/9/9/9/9/4K4 w", FenError::King(Side::Black, 2)),
+            ("4k4/9/9/9/9/9/9/9/9/3KK4 w", FenError::King(Side::White, 2)),
+            ("4k4/9/9/9/9/9/9/9/9/RRR1K4 w", FenError::TooMany(Side::White, Kind::Rook, 3)),
+            ("4k4/9/9/9/pppppp3/9/9/9/9/4K4 w", FenError::TooMany(Side::Black, Kind::Pawn, 6)),
+        ];
+        for (fen, error) in cases {
+            assert_eq!(parse(fen).unwrap_err(), error, "{fen}");
+        }
+        assert_eq!(FenError::King(Side::Black, 2).to_string(), "黑方有2个帅(将)");
+        assert_eq!(FenError::RankCount(9).to_string(), "棋盘应为10行, 实际9行");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for seed in 0..500 {
+            let position = random_position(seed);
+            let fen = serialize(&position);
+            let parsed = parse(&fen).unwrap();
+            assert_eq!(squares(&parsed.broad_map), squares(&position.broad_map), "{fen}");
+            assert_eq!(parsed.side, position.side, "{fen}");
+            assert_eq!(parsed.noeat_move_num, position.noeat_move_num, "{fen}");
+            assert_eq!(parsed.round, position.round, "{fen}");
+            assert_eq!(serialize(&parsed), fen);
+        }
+    }
+}
@@ -1,6 +1,7 @@
-use crate::component::piece::{Kind, Piece, Side};
-use crate::public::{Pos, ROUTE_OFFSET};
-use crate::{chess, player};
+use crate::component::piece::{Piece, Side};
+use crate::public::{self, Pos, ROUTE_OFFSET};
+use crate::status::ChessState;
+use crate::{fen, notation::Notation, player};
 use bevy::prelude::*;
 use chessai::position;
 
@@ -9,6 +10,20 @@ pub enum GameMode {
     AiGame,
     DeduceGame,
     InterGame,
+    WatchGame,
+}
+
+/// 行棋记录, 用于悔棋回滚
+#[derive(Debug, Clone, Copy)]
+pub struct Step {
+    // 起点
+    pub src: Pos,
+    // 终点
+    pub dst: Pos,
+    // 被吃掉的棋子
+    pub captured: Option<Piece>,
+    // 行棋前没有吃子的步数
+    pub noeat_move_num: usize,
 }
 
 #[derive(Resource)]
@@ -33,137 +48,304 @@ pub struct Data {
     pub selected: Option<Piece>,
     // 游戏模式
     pub ai_side: Option<Side>,
+    // 开局局面
+    pub start_fen: String,
+    // 行棋记录
+    pub history: Vec<Step>,
+    // 撤销的走法, 用于前进
+    pub redo: Vec<(Pos, Pos)>,
+    // 本局使用提示的次数
+    pub prompt_num: usize,
+    // 翻转棋盘, 黑方在下
+    pub flipped: bool,
+    // 时限规则
+    pub time_control: player::TimeControl,
+    // AI难度
+    pub ai_level: player::AiLevel,
+    // 打谱时实时显示引擎推荐走法
+    pub analysis: bool,
+    // 回看第几步后的局面, 回看时只显示不能行棋
+    pub preview: Option<usize>,
+    // 棋谱显示的记法
+    pub notation: Notation,
+    // 对局结果, 认输、超时与议和等不能由局面判定的结果也记录在此
+    pub result: Option<chessai::pregen::Winner>,
 }
 
 impl Data {
     pub fn new() -> Self {
         info!("init system data");
+        let position = fen::parse(public::START_POS).unwrap();
         Self {
             engine: chessai::Engine::new(),
             selected: None,
             white_player: player::Player::new_white(),
             black_player: player::Player::new_black(),
-            broad_map: [
-                [
-                    Some(Piece::white(Kind::Rook, 0, 0)),
-                    Some(Piece::white(Kind::Knight, 0, 1)),
-                    Some(Piece::white(Kind::Bishop, 0, 2)),
-                    Some(Piece::white(Kind::Advisor, 0, 3)),
-                    Some(Piece::white(Kind::King, 0, 4)),
-                    Some(Piece::white(Kind::Advisor, 0, 5)),
-                    Some(Piece::white(Kind::Bishop, 0, 6)),
-                    Some(Piece::white(Kind::Knight, 0, 7)),
-                    Some(Piece::white(Kind::Rook, 0, 8)),
-                ],
-                [None, None, None, None, None, None, None, None, None],
-                [
-                    None,
-                    Some(Piece::white(Kind::Cannon, 2, 1)),
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                    Some(Piece::white(Kind::Cannon, 2, 7)),
-                    None,
-                ],
-                [
-                    Some(Piece::white(Kind::Pawn, 3, 0)),
-                    None,
-                    Some(Piece::white(Kind::Pawn, 3, 2)),
-                    None,
-                    Some(Piece::white(Kind::Pawn, 3, 4)),
-                    None,
-                    Some(Piece::white(Kind::Pawn, 3, 6)),
-                    None,
-                    Some(Piece::white(Kind::Pawn, 3, 8)),
-                ],
-                [None, None, None, None, None, None, None, None, None],
-                [None, None, None, None, None, None, None, None, None],
-                [
-                    Some(Piece::black(Kind::Pawn, 6, 0)),
-                    None,
-                    Some(Piece::black(Kind::Pawn, 6, 2)),
-                    None,
-                    Some(Piece::black(Kind::Pawn, 6, 4)),
-                    None,
-                    Some(Piece::black(Kind::Pawn, 6, 6)),
-                    None,
-                    Some(Piece::black(Kind::Pawn, 6, 8)),
-                ],
-                [
-                    None,
-                    Some(Piece::black(Kind::Cannon, 7, 1)),
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                    Some(Piece::black(Kind::Cannon, 7, 7)),
-                    None,
-                ],
-                [None, None, None, None, None, None, None, None, None],
-                [
-                    Some(Piece::black(Kind::Rook, 9, 0)),
-                    Some(Piece::black(Kind::Knight, 9, 1)),
-                    Some(Piece::black(Kind::Bishop, 9, 2)),
-                    Some(Piece::black(Kind::Advisor, 9, 3)),
-                    Some(Piece::black(Kind::King, 9, 4)),
-                    Some(Piece::black(Kind::Advisor, 9, 5)),
-                    Some(Piece::black(Kind::Bishop, 9, 6)),
-                    Some(Piece::black(Kind::Knight, 9, 7)),
-                    Some(Piece::black(Kind::Rook, 9, 8)),
-                ],
-            ],
-            round: 0,
-            noeat_move_num: 0,
+            broad_map: position.broad_map,
+            round: position.round,
+            noeat_move_num: position.noeat_move_num,
             current_side: None,
             mode: None,
             ai_side: None,
+            start_fen: public::START_POS.to_string(),
+            history: Vec::new(),
+            redo: Vec::new(),
+            prompt_num: 0,
+            flipped: false,
+            time_control: player::TimeControl::default(),
+            ai_level: player::AiLevel::default(),
+            analysis: false,
+            preview: None,
+            notation: Notation::default(),
+            result: None,
         }
     }
 
-    pub fn get_last_move(&self) -> Option<(Pos, Pos)> {
-        match self.engine.mv_list.last() {
-            Some(mv) => {
-                let ((src_row, src_col), (dst_row, dst_col)) = position::move2pos(*mv);
-                println!("{src_row}-{src_col} {dst_row}-{dst_col}");
-                Some((Pos::new(src_row, src_col), Pos::new(dst_row, dst_col)))
+    /// 新局: 重建对局数据, 保留对局模式与双方玩家
+    pub fn reset(&mut self) {
+        let mut data = Self::new();
+        data.mode = self.mode;
+        data.ai_side = self.ai_side;
+        data.ai_level = self.ai_level;
+        data.analysis = self.analysis;
+        data.notation = self.notation;
+        data.white_player.id = self.white_player.id;
+        data.black_player.id = self.black_player.id;
+        data.flipped = self.flipped;
+        data.set_time_control(self.time_control);
+        // 从本局的开局局面重新开始, 对局未开始时不设行棋方
+        if let Ok(position) = fen::parse(&self.start_fen) {
+            data.set_position(&position);
+        }
+        data.current_side = self.current_side.and(data.current_side);
+        *self = data;
+    }
+
+    /// 设置AI执棋方, 同步双方玩家身份, 玩家执黑时自动翻转棋盘
+    pub fn set_ai_side(&mut self, side: Side) {
+        self.ai_side = Some(side);
+        self.flipped = side == Side::White;
+        match side {
+            Side::White => {
+                self.white_player.id = player::Id::Ai(self.ai_level);
+                self.black_player.id = player::Id::Home;
+            }
+            Side::Black => {
+                self.white_player.id = player::Id::Home;
+                self.black_player.id = player::Id::Ai(self.ai_level);
             }
-            None => None,
         }
     }
 
-    pub fn to_fen(&self) -> String {
-        let mut fen = String::new();
-        for pieces in self.broad_map.iter() {
-            let mut line = String::new();
-            let mut num = 0;
-            for piece in pieces {
-                if let Some(piece) = piece {
-                    if num > 0 {
-                        line.push_str(&num.to_string());
-                        num = 0;
-                    }
-                    line.push_str(&piece.code());
-                } else {
-                    num += 1;
-                }
+    /// 联机对弈: 设置本机执棋方, 对方为客场方, 本机执黑时自动翻转棋盘
+    pub fn set_home_side(&mut self, side: Side) {
+        self.flipped = side == Side::Black;
+        let (home, away) = match side {
+            Side::White => (&mut self.white_player, &mut self.black_player),
+            Side::Black => (&mut self.black_player, &mut self.white_player),
+        };
+        home.id = player::Id::Home;
+        away.id = player::Id::Away;
+    }
+
+    /// 观战: 双方都是客场玩家, 本机不能行棋
+    pub fn set_spectator(&mut self) {
+        self.flipped = false;
+        self.white_player.id = player::Id::Away;
+        self.black_player.id = player::Id::Away;
+    }
+
+    /// 联机对弈中对方的执棋方
+    pub fn away_side(&self) -> Option<Side> {
+        match (self.white_player.id, self.black_player.id) {
+            (player::Id::Away, _) => Some(Side::White),
+            (_, player::Id::Away) => Some(Side::Black),
+            _ => None,
+        }
+    }
+
+    /// 本机认输的一方: 联机对弈为本机执棋方, 人机对弈为玩家, 打谱时为行棋方
+    pub fn resign_side(&self) -> Option<Side> {
+        match self.mode {
+            Some(GameMode::InterGame) => self.away_side().map(|side| side.opponent()),
+            Some(GameMode::AiGame) => self.ai_side.map(|side| side.opponent()),
+            Some(GameMode::WatchGame) => None,
+            _ => self.current_side,
+        }
+    }
+
+    /// 设置时限规则, 重置双方计时
+    pub fn set_time_control(&mut self, time_control: player::TimeControl) {
+        self.time_control = time_control;
+        self.white_player.set_time_control(time_control);
+        self.black_player.set_time_control(time_control);
+    }
+
+    /// 当前行棋方对应的对局状态
+    pub fn chess_state(&self) -> ChessState {
+        let player = match self.current_side {
+            Some(Side::White) => self.white_player,
+            Some(Side::Black) => self.black_player,
+            None => return ChessState::None,
+        };
+        match player.id {
+            player::Id::Ai(_) => ChessState::AiPlay,
+            player::Id::Home => ChessState::HomePlay,
+            player::Id::Away => ChessState::AwayPlay,
+        }
+    }
+
+    pub fn get_last_move(&self) -> Option<(Pos, Pos)> {
+        self.history.last().map(|step| (step.src, step.dst))
+    }
+
+    /// 行棋: 同步棋盘地图、引擎与行棋记录, 返回被吃掉的棋子
+    pub fn make_move(&mut self, src: Pos, dst: Pos) -> Option<Piece> {
+        // 与撤销的走法相同时保留后续变化, 否则清空
+        if self.redo.last() == Some(&(src, dst)) {
+            self.redo.pop();
+        } else {
+            self.redo.clear();
+        }
+        let mut piece = self.broad_map[src.row][src.col].unwrap();
+        let captured = self.broad_map[dst.row][dst.col];
+        self.history.push(Step {
+            src,
+            dst,
+            captured,
+            noeat_move_num: self.noeat_move_num,
+        });
+        piece.row = dst.row;
+        piece.col = dst.col;
+        self.broad_map[src.row][src.col] = None;
+        self.broad_map[dst.row][dst.col] = Some(piece);
+        self.noeat_move_num = match captured {
+            Some(_) => 0,
+            None => self.noeat_move_num + 1,
+        };
+        engine_move(&mut self.engine, src, dst);
+        captured
+    }
+
+    /// 走法是否合法: 起点须为行棋方的棋子, 引擎判断合法且走后不被将军
+    pub fn can_move(&mut self, src: Pos, dst: Pos) -> bool {
+        let side = self.current_side.unwrap();
+        if !self.broad_map[src.row][src.col].is_some_and(|piece| piece.side == side) {
+            return false;
+        }
+        let mv = position::iccs2move(&position::pos2iccs(src.row, src.col, dst.row, dst.col));
+        if !self.engine.legal_move(mv) || !self.engine.make_move(mv) {
+            return false;
+        }
+        self.engine.undo_make_move();
+        true
+    }
+
+    /// 检查后行棋, 行棋后换边
+    pub fn try_move(&mut self, src: Pos, dst: Pos) -> bool {
+        if !self.can_move(src, dst) {
+            return false;
+        }
+        self.make_move(src, dst);
+        self.change_side();
+        true
+    }
+
+    /// 悔棋: 撤销最后一步, 恢复棋盘地图、回合数与行棋方, 并重建引擎局面
+    pub fn undo_move(&mut self) -> Option<Step> {
+        let step = self.history.pop()?;
+        self.preview = None;
+        let mut piece = self.broad_map[step.dst.row][step.dst.col].unwrap();
+        piece.row = step.src.row;
+        piece.col = step.src.col;
+        self.broad_map[step.src.row][step.src.col] = Some(piece);
+        self.broad_map[step.dst.row][step.dst.col] = step.captured;
+        self.noeat_move_num = step.noeat_move_num;
+        self.redo.push((step.src, step.dst));
+
+        // 撤销换边
+        match self.current_side.unwrap() {
+            Side::White => {
+                self.current_side = Some(Side::Black);
             }
-            if num > 0 {
-                line.push_str(&num.to_string());
+            Side::Black => {
+                self.round -= 1;
+                self.current_side = Some(Side::White);
             }
-            fen.push_str(&line);
-            fen.push_str("/")
         }
-        fen.pop().unwrap();
-        fen.push_str(&format!(
-            " {} -- {} {}",
-            self.current_side.unwrap().code(),
-            self.noeat_move_num,
-            self.round
-        ));
-        fen
+
+        // 引擎从开局重新走到当前局面
+        self.engine.from_fen(&self.start_fen);
+        for step in self.history.iter() {
+            engine_move(&mut self.engine, step.src, step.dst);
+        }
+        Some(step)
+    }
+
+    /// 前进: 重走最近撤销的一步并换边
+    pub fn redo_move(&mut self) -> Option<(Pos, Pos)> {
+        let (src, dst) = *self.redo.last()?;
+        self.preview = None;
+        self.make_move(src, dst);
+        self.change_side();
+        Some((src, dst))
+    }
+
+    /// 走完前serial步后的棋盘地图, 由当前局面逐步撤销得到
+    pub fn position_at(&self, serial: usize) -> [[Option<Piece>; 9]; 10] {
+        let mut broad_map = self.broad_map;
+        for step in self.history.iter().skip(serial).rev() {
+            let mut piece = broad_map[step.dst.row][step.dst.col].unwrap();
+            piece.row = step.src.row;
+            piece.col = step.src.col;
+            broad_map[step.src.row][step.src.col] = Some(piece);
+            broad_map[step.dst.row][step.dst.col] = step.captured;
+        }
+        broad_map
+    }
+
+    /// 当前局面, 对局开始前按红方行棋
+    pub fn position(&self) -> fen::Position {
+        fen::Position {
+            broad_map: self.broad_map,
+            side: self.current_side.unwrap_or(Side::White),
+            noeat_move_num: self.noeat_move_num,
+            round: self.round,
+        }
+    }
+
+    pub fn to_fen(&self) -> String {
+        fen::serialize(&self.position())
+    }
+
+    /// 载入FEN局面
+    pub fn load_fen(&mut self, fen: &str) -> Result<(), fen::FenError> {
+        self.set_position(&fen::parse(fen)?);
+        Ok(())
+    }
+
+    /// 以指定局面为开局: 同步棋盘地图、行棋方与引擎, 清空行棋记录
+    pub fn set_position(&mut self, position: &fen::Position) {
+        self.broad_map = position.broad_map;
+        self.current_side = Some(position.side);
+        self.noeat_move_num = position.noeat_move_num;
+        self.round = position.round;
+        self.start_fen = fen::serialize(position);
+        self.engine.from_fen(&self.start_fen);
+        self.history.clear();
+        self.redo.clear();
+        self.selected = None;
+        self.preview = None;
+        self.result = None;
+    }
+
+    /// 引擎对一方的局面评估, 引擎按行棋方给分, 对方视角取反
+    pub fn evaluate(&self, side: Side) -> isize {
+        let vl = self.engine.evaluate();
+        match self.current_side {
+            Some(current) if current != side => -vl,
+            _ => vl,
+        }
     }
 
     pub fn get_current_player(&mut self) -> &mut player::Player {
@@ -177,7 +359,7 @@ impl Data {
     pub fn change_side(&mut self) {
         match self.current_side.unwrap() {
             Side::White => {
-                self.round+=1;
+                self.round += 1;
                 self.current_side = Some(Side::Black);
             }
             Side::Black => {
@@ -196,6 +378,12 @@ impl Data {
     }
 }
 
+/// 引擎同步行棋, 走法须先经过can_move检查, 否则棋盘地图与引擎不一致
+fn engine_move(engine: &mut chessai::Engine, src: Pos, dst: Pos) {
+    let iccs = position::pos2iccs(src.row, src.col, dst.row, dst.col);
+    assert!(engine.make_move(position::iccs2move(&iccs)), "引擎拒绝走法 {iccs}");
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -209,6 +397,154 @@ mod tests {
         assert_eq!((dst_row, dst_col), (9, 8));
     }
 
+    #[test]
+    fn test_undo_redo() {
+        let mut data = Data::new();
+        data.current_side = Some(Side::White);
+        data.engine.from_fen(public::START_POS);
+        let moves = [
+            (Pos::new(2, 7), Pos::new(2, 4)),
+            (Pos::new(9, 7), Pos::new(7, 6)),
+            (Pos::new(0, 7), Pos::new(2, 6)),
+        ];
+        for (src, dst) in moves {
+            data.make_move(src, dst);
+            data.change_side();
+        }
+        let fen = data.engine.to_fen();
+
+        // 后退到开局
+        while data.undo_move().is_some() {}
+        assert_eq!(data.current_side, Some(Side::White));
+        assert_eq!(data.round, 0);
+        assert_eq!(data.redo.len(), 3);
+
+        // 重走第一步保留后续变化
+        data.make_move(moves[0].0, moves[0].1);
+        data.change_side();
+        assert_eq!(data.redo.len(), 2);
+        while data.redo_move().is_some() {}
+        assert_eq!(data.engine.to_fen(), fen);
+        assert_eq!(data.history.len(), 3);
+
+        // 走出新变化后不能再前进
+        data.undo_move();
+        data.make_move(Pos::new(0, 1), Pos::new(2, 2));
+        assert!(data.redo.is_empty());
+    }
+
+    #[test]
+    fn test_can_move() {
+        let mut data = Data::new();
+        data.load_fen("4k4/4a4/9/9/9/9/9/9/9/4RK3 b - - 0 1").unwrap();
+        // 士被红车牵制, 走开后将被将军
+        assert!(!data.can_move(Pos::new(8, 4), Pos::new(7, 3)));
+        // 不能走对方的棋子
+        assert!(!data.can_move(Pos::new(0, 4), Pos::new(1, 4)));
+        assert!(data.can_move(Pos::new(9, 4), Pos::new(9, 3)));
+        assert_eq!(data.engine.to_fen(), "4k4/4a4/9/9/9/9/9/9/9/4RK3 b");
+        assert!(data.try_move(Pos::new(9, 4), Pos::new(9, 3)));
+        assert_eq!(data.current_side, Some(Side::White));
+    }
+
+    #[test]
+    fn test_resign_side() {
+        // 联机对弈轮到对方行棋时, 认输的仍是本机执棋方
+        let mut data = Data::new();
+        data.mode = Some(GameMode::InterGame);
+        data.set_home_side(Side::White);
+        data.current_side = Some(Side::Black);
+        assert_eq!(data.chess_state(), ChessState::AwayPlay);
+        assert_eq!(data.resign_side(), Some(Side::White));
+
+        data.mode = Some(GameMode::AiGame);
+        data.set_ai_side(Side::White);
+        assert_eq!(data.resign_side(), Some(Side::Black));
+        data.mode = Some(GameMode::DeduceGame);
+        assert_eq!(data.resign_side(), Some(Side::Black));
+        data.mode = Some(GameMode::WatchGame);
+        assert_eq!(data.resign_side(), None);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut data = Data::new();
+        data.engine.from_fen("3k5/9/9/9/9/9/9/9/9/R3K4 b - - 0 1");
+        data.current_side = Some(Side::Black);
+        // 红方多一车, 评估分与行棋方无关
+        assert!(data.evaluate(Side::White) > 100);
+        assert_eq!(data.evaluate(Side::Black), -data.evaluate(Side::White));
+        data.engine.from_fen("3k5/9/9/9/9/9/9/9/9/R3K4 w - - 0 1");
+        data.current_side = Some(Side::White);
+        assert!(data.evaluate(Side::White) > 100);
+        assert!(data.evaluate(Side::Black) < -100);
+    }
+
+    #[test]
+    fn test_position_at() {
+        fn codes(broad_map: &[[Option<Piece>; 9]; 10]) -> Vec<String> {
+            broad_map
+                .iter()
+                .flatten()
+                .map(|piece| piece.map_or(String::from("."), |piece| piece.code()))
+                .collect()
+        }
+        let mut data = Data::new();
+        data.current_side = Some(Side::White);
+        data.engine.from_fen(public::START_POS);
+        let start = codes(&data.broad_map);
+        let moves = [
+            (Pos::new(2, 7), Pos::new(2, 4)),
+            (Pos::new(9, 7), Pos::new(7, 6)),
+            // 炮五进四吃卒
+            (Pos::new(2, 4), Pos::new(6, 4)),
+        ];
+        let mut positions = vec![start];
+        for (src, dst) in moves {
+            data.make_move(src, dst);
+            data.change_side();
+            positions.push(codes(&data.broad_map));
+        }
+        for (serial, position) in positions.iter().enumerate() {
+            assert_eq!(&codes(&data.position_at(serial)), position);
+        }
+        let piece = data.position_at(1)[2][4].unwrap();
+        assert_eq!((piece.row, piece.col), (2, 4));
+    }
+
+    #[test]
+    fn test_fen() {
+        // 对局开始前按红方行棋生成
+        let mut data = Data::new();
+        assert_eq!(data.to_fen(), public::START_POS);
+        data.load_fen(public::START_POS).unwrap();
+        data.make_move(Pos::new(2, 7), Pos::new(2, 4));
+        data.change_side();
+        data.make_move(Pos::new(9, 7), Pos::new(7, 6));
+        data.change_side();
+        assert_eq!(
+            data.to_fen(),
+            "rnbakab1r/9/1c4nc1/p1p1p1p1p/9/9/P1P1P1P1P/1C2C4/9/RNBAKABNR w - - 2 2"
+        );
+    }
+
+    #[test]
+    fn test_fen_round_trip() {
+        use fen::testing::{random_position, squares};
+        for seed in 0..500 {
+            let mut data = Data::new();
+            data.set_position(&random_position(seed));
+            let fen = data.to_fen();
+            let mut loaded = Data::new();
+            loaded.load_fen(&fen).unwrap();
+            assert_eq!(squares(&loaded.broad_map), squares(&data.broad_map), "{fen}");
+            assert_eq!(loaded.current_side, data.current_side, "{fen}");
+            assert_eq!(loaded.noeat_move_num, data.noeat_move_num, "{fen}");
+            assert_eq!(loaded.round, data.round, "{fen}");
+            assert_eq!(loaded.start_fen, fen);
+        }
+    }
+
     #[test]
     fn test_match() {
         let n = 9;
@@ -7,14 +7,21 @@ use std::io::Cursor;
 use winit::window::Icon;
 
 use public::WIN_SIZE;
+mod book;
 mod chess;
+mod clipboard;
 mod component;
 mod event;
+mod fen;
 mod game;
+mod net;
+mod notation;
+mod pgn;
 mod player;
 mod public;
 mod setup;
 mod status;
+mod xqf;
 
 fn main() {
     App::new()
@@ -31,6 +38,10 @@ fn main() {
         // 初始化数据
         .insert_resource(game::Data::new())
         .insert_resource(public::BroadEntitys::default())
+        // 开局库
+        .insert_resource(book::Book::new())
+        // 剪贴板
+        .insert_non_send_resource(clipboard::Clipboard::default())
         // 窗口图标
         .add_systems(Startup, set_window_icon)
         // 加载退出游戏系统
@@ -41,12 +52,44 @@ fn main() {
         .add_systems(OnExit(GameState::PENDING), status::pending::exit_state)
         // IN PENDING
         .add_systems(Update, status::pending::in_state.run_if(in_state(GameState::PENDING)))
+        // 联机大厅
+        .add_systems(
+            Update,
+            (
+                status::lobby::in_state,
+                status::lobby::address_input,
+                status::lobby::connect_system.run_if(resource_exists::<net::NetConnection>()),
+                status::lobby::browse_system.run_if(resource_exists::<net::discovery::Browser>()),
+            )
+                .run_if(in_state(GameState::PENDING)),
+        )
+        // 摆棋
+        .add_systems(
+            Update,
+            (
+                status::editor::editor_button_system,
+                status::editor::drag_system,
+                status::editor::render_system
+                    .after(status::editor::editor_button_system)
+                    .after(status::editor::drag_system),
+            )
+                .run_if(in_state(GameState::PENDING))
+                .run_if(resource_exists::<status::editor::Editor>()),
+        )
         // 进入PAUSED状态
         .add_systems(OnEnter(GameState::PAUSED), status::paused::enter_state)
         // 退出PAUSED状态
         .add_systems(OnExit(GameState::PAUSED), status::paused::exit_state)
         // IN PAUSED
         .add_systems(Update, status::paused::in_state.run_if(in_state(GameState::PAUSED)))
+        // paused to pending
+        .add_systems(
+            OnTransition {
+                from: GameState::PAUSED,
+                to: GameState::PENDING,
+            },
+            status::pending::from_paused_enter,
+        )
         // pending to running
         .add_systems(
             OnTransition {
@@ -110,7 +153,7 @@ fn set_window_icon(
     };
 }
 /**
-todo 
+todo
 拆分系统
 1. 选择棋子(动画渲染)
 2. 移动棋子(是否将军，是否死亡，是否吃子，动画)
@@ -0,0 +1,276 @@
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+
+use crate::player::TIME_CONTROLS;
+
+// 局域网发现端口, 同一台机器上的多个主场方依次占用后续端口
+pub const DISCOVERY_PORT: u16 = 9528;
+pub const DISCOVERY_PORTS: u16 = 8;
+// 重新搜索对局的间隔
+pub const SEARCH_INTERVAL: Duration = Duration::from_secs(1);
+// 超过该时间没有应答的对局视为已关闭
+const GAME_EXPIRE: Duration = Duration::from_secs(3);
+// 主场方等待查询的超时, 用于检查是否停止广播
+const READ_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// 发现协议消息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discovery {
+    // 搜索局域网内的对局
+    Find,
+    // 主场方应答: 实例标识, 对局端口, 时限规则序号, 是否已开局
+    Game(u64, u16, usize, bool),
+}
+
+impl Discovery {
+    pub fn encode(&self) -> String {
+        match self {
+            Discovery::Find => String::from("XQ FIND"),
+            Discovery::Game(id, port, time_control, playing) => {
+                let state = match playing {
+                    true => "playing",
+                    false => "open",
+                };
+                format!("XQ GAME {:016x} {} {} {}", id, port, time_control, state)
+            }
+        }
+    }
+
+    pub fn decode(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields[..] {
+            ["XQ", "FIND"] => Some(Discovery::Find),
+            ["XQ", "GAME", id, port, time_control, state] => {
+                let id = u64::from_str_radix(id, 16).ok()?;
+                let port = port.parse().ok()?;
+                let time_control: usize = time_control.parse().ok()?;
+                if time_control >= TIME_CONTROLS.len() {
+                    return None;
+                }
+                let playing = match state {
+                    "open" => false,
+                    "playing" => true,
+                    _ => return None,
+                };
+                Some(Discovery::Game(id, port, time_control, playing))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// 局域网内的公开对局
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenGame {
+    // 主场方实例标识, 同一对局经回环与局域网两条路径应答时去重
+    pub id: u64,
+    // 加入对局的地址
+    pub addr: SocketAddr,
+    // 时限规则序号
+    pub time_control: usize,
+    // 已开局, 只能观战
+    pub playing: bool,
+    last_seen: Instant,
+}
+
+/// 主场方广播: 在后台应答局域网内的搜索, 开局后继续广播供观众加入, 移除资源即停止
+#[derive(Resource)]
+pub struct Advertiser {
+    pub port: u16,
+    playing: Arc<AtomicBool>,
+    closed: Arc<AtomicBool>,
+}
+
+impl Advertiser {
+    /// 占用第一个空闲的发现端口, 应答对局端口与时限规则
+    pub fn new(game_port: u16, time_control: usize) -> io::Result<Self> {
+        let socket = bind_discovery_port()?;
+        socket.set_read_timeout(Some(READ_TIMEOUT))?;
+        let port = socket.local_addr()?.port();
+        let closed = Arc::new(AtomicBool::new(false));
+        let playing = Arc::new(AtomicBool::new(false));
+        let advertiser = Self {
+            port,
+            playing: playing.clone(),
+            closed: closed.clone(),
+        };
+        let id = rand::random();
+        info!("局域网广播 发现端口:{} 对局端口:{game_port}", advertiser.port);
+        thread::spawn(move || {
+            let mut buf = [0_u8; 128];
+            while !closed.load(Ordering::Relaxed) {
+                let (len, src) = match socket.recv_from(&mut buf) {
+                    Ok(recv) => recv,
+                    Err(_) => continue,
+                };
+                let line = String::from_utf8_lossy(&buf[..len]);
+                if Discovery::decode(&line) == Some(Discovery::Find) {
+                    let playing = playing.load(Ordering::Relaxed);
+                    let reply = Discovery::Game(id, game_port, time_control, playing).encode();
+                    socket.send_to(reply.as_bytes(), src).ok();
+                }
+            }
+        });
+        Ok(advertiser)
+    }
+
+    /// 开局后标记为观战
+    pub fn set_playing(&self) {
+        self.playing.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Advertiser {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+}
+
+fn bind_discovery_port() -> io::Result<UdpSocket> {
+    let mut last_err = None;
+    for port in DISCOVERY_PORT..DISCOVERY_PORT + DISCOVERY_PORTS {
+        match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port)) {
+            Ok(socket) => return Ok(socket),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(last_err.unwrap())
+}
+
+/// 客场方搜索: 向局域网广播与本机回环发送查询, 收集主场方的应答
+#[derive(Resource)]
+pub struct Browser {
+    socket: UdpSocket,
+    games: Vec<OpenGame>,
+    last_search: Option<Instant>,
+}
+
+impl Browser {
+    pub fn new() -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            games: Vec::new(),
+            last_search: None,
+        })
+    }
+
+    /// 发送一次查询, 本机回环保证同一台机器上的多个实例可以互相发现
+    pub fn search(&mut self) {
+        let find = Discovery::Find.encode();
+        for port in DISCOVERY_PORT..DISCOVERY_PORT + DISCOVERY_PORTS {
+            for ip in [Ipv4Addr::BROADCAST, Ipv4Addr::LOCALHOST] {
+                self.socket.send_to(find.as_bytes(), (ip, port)).ok();
+            }
+        }
+        self.last_search = Some(Instant::now());
+    }
+
+    /// 定时查询并接收应答, 对局列表有变化时返回true
+    pub fn poll(&mut self) -> bool {
+        if self.last_search.is_none_or(|last| last.elapsed() >= SEARCH_INTERVAL) {
+            self.search();
+        }
+
+        let mut changed = false;
+        let mut buf = [0_u8; 128];
+        while let Ok((len, src)) = self.socket.recv_from(&mut buf) {
+            let (id, port, time_control, playing) =
+                match Discovery::decode(&String::from_utf8_lossy(&buf[..len])) {
+                    Some(Discovery::Game(id, port, time_control, playing)) => {
+                        (id, port, time_control, playing)
+                    }
+                    _ => continue,
+                };
+            match self.games.iter_mut().find(|game| game.id == id) {
+                Some(game) => {
+                    changed |= game.playing != playing;
+                    game.playing = playing;
+                    game.last_seen = Instant::now();
+                }
+                None => {
+                    let addr = SocketAddr::new(src.ip(), port);
+                    info!("发现对局 {addr}");
+                    self.games.push(OpenGame {
+                        id,
+                        addr,
+                        time_control,
+                        playing,
+                        last_seen: Instant::now(),
+                    });
+                    changed = true;
+                }
+            }
+        }
+
+        let count = self.games.len();
+        self.games.retain(|game| game.last_seen.elapsed() < GAME_EXPIRE);
+        changed || count != self.games.len()
+    }
+
+    pub fn games(&self) -> &[OpenGame] {
+        &self.games
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        for message in [
+            Discovery::Find,
+            Discovery::Game(u64::MAX, 9527, 2, false),
+            Discovery::Game(1, 9527, 0, true),
+        ] {
+            assert_eq!(Discovery::decode(&message.encode()), Some(message));
+        }
+        for line in [
+            "",
+            "XQ",
+            "XQ FIND now",
+            "XQ GAME 1 9527 0",
+            "XQ GAME x 9527 0 open",
+            "XQ GAME 1 70000 0 open",
+            "XQ GAME 1 9527 99 open",
+            "XQ GAME 1 9527 0 closed",
+        ] {
+            assert_eq!(Discovery::decode(line), None, "{line}");
+        }
+    }
+
+    #[test]
+    fn test_discover_local_games() {
+        // 同一台机器上的两个主场方
+        let first = Advertiser::new(10001, 0).unwrap();
+        let second = Advertiser::new(10002, 1).unwrap();
+        assert_ne!(first.port, second.port);
+        second.set_playing();
+
+        let mut browser = Browser::new().unwrap();
+        let start = Instant::now();
+        while browser.games().iter().filter(|game| game.addr.port() > 10000).count() < 2 {
+            browser.poll();
+            assert!(start.elapsed() < Duration::from_secs(5), "搜索对局超时");
+            thread::sleep(Duration::from_millis(10));
+        }
+        // 只关心本测试创建的对局
+        let mut ports: Vec<(u16, usize, bool)> = browser
+            .games()
+            .iter()
+            .map(|game| (game.addr.port(), game.time_control, game.playing))
+            .filter(|(port, _, _)| [10001, 10002].contains(port))
+            .collect();
+        ports.sort();
+        assert_eq!(ports, vec![(10001, 0, false), (10002, 1, true)]);
+    }
+}
@@ -0,0 +1,459 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+
+pub mod discovery;
+pub mod protocol;
+
+pub use protocol::{Message, Resync};
+
+// 默认联机端口
+pub const NET_PORT: u16 = 9527;
+// 等待对手加入时的轮询间隔
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+// 加入对局的连接超时
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// 断线后重新连接主场方的间隔
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+// 新连接发送身份的超时
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+// 写线程检查连接是否断开的间隔
+const WRITE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 网络事件, 由后台线程产生, 在系统中轮询
+#[derive(Debug)]
+pub enum NetEvent {
+    // 连接建立
+    Connected,
+    // 收到消息
+    Message(Message),
+    // 连接断开或连接失败
+    Disconnected,
+    // 有观众加入(仅主场方)
+    Spectator,
+}
+
+/// 联机连接, 读写均在后台线程中进行, 移除资源即关闭连接
+/// 对局开始后断线, 主场方继续等待对手重连, 客场方定时重新连接
+#[derive(Resource)]
+pub struct NetConnection {
+    // 本机创建对局(主场)
+    pub host: bool,
+    // 本机以观众身份观战
+    pub spectator: bool,
+    // 本机监听端口, 加入对局时为0
+    pub port: u16,
+    sender: Sender<Message>,
+    receiver: Mutex<Receiver<NetEvent>>,
+    closed: Arc<AtomicBool>,
+    spectators: Arc<Mutex<Vec<Spectator>>>,
+}
+
+/// 主场方的观众连接, 只发送不接收
+struct Spectator {
+    sender: Sender<Message>,
+    alive: Arc<AtomicBool>,
+}
+
+/// 后台线程持有的通道, 主场方每个新连接各持一份
+#[derive(Clone)]
+struct Channels {
+    messages: Arc<Mutex<Receiver<Message>>>,
+    events: Sender<NetEvent>,
+    closed: Arc<AtomicBool>,
+    spectators: Arc<Mutex<Vec<Spectator>>>,
+}
+
+impl NetConnection {
+    fn new(host: bool, spectator: bool, port: u16) -> (Self, Channels) {
+        let (sender, messages) = mpsc::channel();
+        let (events, receiver) = mpsc::channel();
+        let closed = Arc::new(AtomicBool::new(false));
+        let spectators = Arc::new(Mutex::new(Vec::new()));
+        let connection = Self {
+            host,
+            spectator,
+            port,
+            sender,
+            receiver: Mutex::new(receiver),
+            closed: closed.clone(),
+            spectators: spectators.clone(),
+        };
+        (
+            connection,
+            Channels {
+                messages: Arc::new(Mutex::new(messages)),
+                events,
+                closed,
+                spectators,
+            },
+        )
+    }
+
+    /// 创建对局: 监听端口, 在后台等待对手与观众加入
+    pub fn host(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        let (connection, channels) = Self::new(true, false, listener.local_addr()?.port());
+        info!("创建对局 端口:{}", connection.port);
+        thread::spawn(move || {
+            while let Some(stream) = accept(&listener, &channels) {
+                // 在新线程中确认身份, 不影响后续连接
+                let channels = channels.clone();
+                thread::spawn(move || handshake(stream, &channels));
+            }
+        });
+        Ok(connection)
+    }
+
+    /// 加入对局: 在后台连接主场方
+    pub fn join(addr: String) -> Self {
+        info!("加入对局 {addr}");
+        Self::connect(addr, false)
+    }
+
+    /// 观战: 以观众身份连接主场方, 只接收对局走法
+    pub fn watch(addr: String) -> Self {
+        info!("观战 {addr}");
+        Self::connect(addr, true)
+    }
+
+    fn connect(addr: String, spectator: bool) -> Self {
+        let (connection, channels) = Self::new(false, spectator, 0);
+        let hello = match spectator {
+            true => Message::Watch,
+            false => Message::Join,
+        };
+        thread::spawn(move || {
+            // 连接后先发送身份
+            let connect = || {
+                addr.to_socket_addrs()
+                    .ok()
+                    .and_then(|mut addrs| addrs.next())
+                    .and_then(|addr| TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).ok())
+                    .and_then(|mut stream| {
+                        writeln!(stream, "{}", hello.encode()).ok().map(|_| stream)
+                    })
+            };
+            let mut stream = match connect() {
+                Some(stream) => stream,
+                None => {
+                    warn!("连接失败 {addr}");
+                    channels.events.send(NetEvent::Disconnected).ok();
+                    return;
+                }
+            };
+            loop {
+                match stream.try_clone() {
+                    Ok(reader) => {
+                        let messages = channels.messages.lock().unwrap();
+                        run(BufReader::new(reader), stream, &channels, &messages);
+                    }
+                    Err(_) => {
+                        channels.events.send(NetEvent::Disconnected).ok();
+                    }
+                }
+                // 断线重连, 直到重新连上或移除连接资源
+                stream = loop {
+                    thread::sleep(RECONNECT_INTERVAL);
+                    if channels.closed.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    if let Some(stream) = connect() {
+                        info!("重新连接 {addr}");
+                        break stream;
+                    }
+                };
+            }
+        });
+        connection
+    }
+
+    pub fn send(&self, message: Message) {
+        info!("发送 {}", message.encode());
+        self.sender.send(message).ok();
+    }
+
+    /// 主场方把消息发给全部观众
+    pub fn broadcast(&self, message: Message) {
+        let mut spectators = self.spectators.lock().unwrap();
+        spectators.retain(|spectator| spectator.alive.load(Ordering::Relaxed));
+        for spectator in spectators.iter() {
+            spectator.sender.send(message.clone()).ok();
+        }
+    }
+
+    /// 当前观众人数
+    pub fn spectator_count(&self) -> usize {
+        self.spectators
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|spectator| spectator.alive.load(Ordering::Relaxed))
+            .count()
+    }
+
+    /// 取出后台线程产生的下一个事件, 未处理的事件留待下一帧
+    pub fn poll(&self) -> Option<NetEvent> {
+        self.receiver.lock().unwrap().try_recv().ok()
+    }
+}
+
+impl Drop for NetConnection {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+}
+
+/// 等待新连接, 连接资源移除或监听出错时返回None
+fn accept(listener: &TcpListener, channels: &Channels) -> Option<TcpStream> {
+    loop {
+        if channels.closed.load(Ordering::Relaxed) {
+            return None;
+        }
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                info!("新连接 {addr}");
+                return Some(stream);
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_INTERVAL);
+            }
+            Err(err) => {
+                warn!("等待对手失败 {err}");
+                channels.events.send(NetEvent::Disconnected).ok();
+                return None;
+            }
+        }
+    }
+}
+
+/// 主场方读取新连接的身份: 对手同一时间只有一个, 观众不限
+fn handshake(stream: TcpStream, channels: &Channels) {
+    if stream.set_nonblocking(false).is_err()
+        || stream.set_read_timeout(Some(HELLO_TIMEOUT)).is_err()
+    {
+        return;
+    }
+    let mut reader = match stream.try_clone() {
+        Ok(reader) => BufReader::new(reader),
+        Err(_) => return,
+    };
+    let mut line = String::new();
+    reader.read_line(&mut line).ok();
+    stream.set_read_timeout(None).ok();
+    match Message::decode(line.trim_end()) {
+        Some(Message::Join) => {
+            // 对手重连时等待上一个连接结束
+            let deadline = Instant::now() + HELLO_TIMEOUT;
+            loop {
+                if let Ok(messages) = channels.messages.try_lock() {
+                    info!("对手加入");
+                    run(reader, stream, channels, &messages);
+                    return;
+                }
+                if Instant::now() >= deadline {
+                    warn!("对局已有对手, 拒绝加入");
+                    return;
+                }
+                thread::sleep(WRITE_INTERVAL);
+            }
+        }
+        Some(Message::Watch) => {
+            info!("观众加入");
+            spectate(reader, stream, channels);
+        }
+        _ => warn!("未知的连接 {}", line.trim_end()),
+    }
+}
+
+/// 连接建立后: 读线程逐行解析消息, 当前线程负责写出消息, 连接断开后返回
+fn run(
+    reader: BufReader<TcpStream>,
+    mut writer: TcpStream,
+    channels: &Channels,
+    messages: &Receiver<Message>,
+) {
+    writer.set_nodelay(true).ok();
+    // 丢弃断线期间积压的消息, 重连后由对局同步补发
+    messages.try_iter().for_each(drop);
+    channels.events.send(NetEvent::Connected).ok();
+
+    let alive = Arc::new(AtomicBool::new(true));
+    let reader_alive = alive.clone();
+    let events = channels.events.clone();
+    thread::spawn(move || {
+        for line in reader.lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            match Message::decode(&line) {
+                Some(message) => {
+                    if events.send(NetEvent::Message(message)).is_err() {
+                        return;
+                    }
+                }
+                None => warn!("无法解析的消息: {line}"),
+            }
+        }
+        reader_alive.store(false, Ordering::Relaxed);
+        events.send(NetEvent::Disconnected).ok();
+    });
+
+    write_loop(&mut writer, messages, &alive, &channels.closed);
+}
+
+/// 主场方的观众连接: 只写出广播的消息, 读到连接关闭即离开
+fn spectate(reader: BufReader<TcpStream>, mut writer: TcpStream, channels: &Channels) {
+    writer.set_nodelay(true).ok();
+    let (sender, messages) = mpsc::channel();
+    let alive = Arc::new(AtomicBool::new(true));
+    channels.spectators.lock().unwrap().push(Spectator {
+        sender,
+        alive: alive.clone(),
+    });
+    channels.events.send(NetEvent::Spectator).ok();
+
+    let reader_alive = alive.clone();
+    thread::spawn(move || {
+        reader.lines().take_while(Result::is_ok).for_each(drop);
+        reader_alive.store(false, Ordering::Relaxed);
+    });
+
+    write_loop(&mut writer, &messages, &alive, &channels.closed);
+    alive.store(false, Ordering::Relaxed);
+}
+
+/// 写出消息直到连接断开或连接资源移除, 最后关闭连接以结束读线程
+fn write_loop(
+    writer: &mut TcpStream,
+    messages: &Receiver<Message>,
+    alive: &AtomicBool,
+    closed: &AtomicBool,
+) {
+    while alive.load(Ordering::Relaxed) && !closed.load(Ordering::Relaxed) {
+        match messages.recv_timeout(WRITE_INTERVAL) {
+            Ok(message) => {
+                if writeln!(writer, "{}", message.encode()).is_err() {
+                    break;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    writer.shutdown(Shutdown::Both).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wait_event(connection: &NetConnection) -> NetEvent {
+        let start = Instant::now();
+        loop {
+            if let Some(event) = connection.poll() {
+                return event;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5), "等待网络事件超时");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_localhost_game() {
+        let host = NetConnection::host(0).unwrap();
+        let guest = NetConnection::join(format!("127.0.0.1:{}", host.port));
+        assert!(matches!(wait_event(&host), NetEvent::Connected));
+        assert!(matches!(wait_event(&guest), NetEvent::Connected));
+
+        host.send(Message::Move(1, String::from("h2e2")));
+        match wait_event(&guest) {
+            NetEvent::Message(message) => {
+                assert_eq!(message, Message::Move(1, String::from("h2e2")))
+            }
+            event => panic!("{event:?}"),
+        }
+        guest.send(Message::Move(2, String::from("h9g7")));
+        match wait_event(&host) {
+            NetEvent::Message(message) => {
+                assert_eq!(message, Message::Move(2, String::from("h9g7")))
+            }
+            event => panic!("{event:?}"),
+        }
+
+        // 一方退出, 另一方收到断线
+        drop(guest);
+        assert!(matches!(wait_event(&host), NetEvent::Disconnected));
+    }
+
+    #[test]
+    fn test_host_reconnect() {
+        let host = NetConnection::host(0).unwrap();
+        let guest = NetConnection::join(format!("127.0.0.1:{}", host.port));
+        assert!(matches!(wait_event(&host), NetEvent::Connected));
+        assert!(matches!(wait_event(&guest), NetEvent::Connected));
+        drop(guest);
+        assert!(matches!(wait_event(&host), NetEvent::Disconnected));
+
+        // 断线后主场方继续等待, 对手重新加入
+        let guest = NetConnection::join(format!("127.0.0.1:{}", host.port));
+        assert!(matches!(wait_event(&host), NetEvent::Connected));
+        assert!(matches!(wait_event(&guest), NetEvent::Connected));
+        guest.send(Message::Ack(1));
+        match wait_event(&host) {
+            NetEvent::Message(message) => assert_eq!(message, Message::Ack(1)),
+            event => panic!("{event:?}"),
+        }
+    }
+
+    #[test]
+    fn test_spectator() {
+        let host = NetConnection::host(0).unwrap();
+        let guest = NetConnection::join(format!("127.0.0.1:{}", host.port));
+        assert!(matches!(wait_event(&host), NetEvent::Connected));
+        assert!(matches!(wait_event(&guest), NetEvent::Connected));
+
+        let watcher = NetConnection::watch(format!("127.0.0.1:{}", host.port));
+        assert!(matches!(wait_event(&watcher), NetEvent::Connected));
+        assert!(matches!(wait_event(&host), NetEvent::Spectator));
+        assert_eq!(host.spectator_count(), 1);
+
+        // 观众只收到广播, 对手只收到对局消息
+        host.broadcast(Message::Move(1, String::from("h2e2")));
+        host.send(Message::DrawOffer);
+        match wait_event(&watcher) {
+            NetEvent::Message(message) => {
+                assert_eq!(message, Message::Move(1, String::from("h2e2")))
+            }
+            event => panic!("{event:?}"),
+        }
+        match wait_event(&guest) {
+            NetEvent::Message(message) => assert_eq!(message, Message::DrawOffer),
+            event => panic!("{event:?}"),
+        }
+
+        // 观众离开
+        drop(watcher);
+        let start = Instant::now();
+        while host.spectator_count() > 0 {
+            assert!(start.elapsed() < Duration::from_secs(5), "观众离开超时");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_join_failed() {
+        // 绑定后立即释放, 得到一个无人监听的端口
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let guest = NetConnection::join(format!("127.0.0.1:{port}"));
+        assert!(matches!(wait_event(&guest), NetEvent::Disconnected));
+    }
+}
@@ -0,0 +1,272 @@
+use crate::component::piece::Side;
+use crate::player::TIME_CONTROLS;
+
+// 聊天消息的最大字数
+pub const CHAT_MAX_LEN: usize = 40;
+
+/// 联机消息, 每条消息编码为一行文本
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    // 连接后首先发送: 以对手身份加入
+    Join,
+    // 连接后首先发送: 以观众身份观战
+    Watch,
+    // 开局, 主场方告知客场方的执棋方与时限规则序号
+    Start(Side, usize),
+    // 行棋, 序号为走完这步后的总步数, ICCS走法
+    Move(usize, String),
+    // 确认收到对应序号的走法
+    Ack(usize),
+    // 重连后同步对局: 全部走法与当前局面FEN
+    Sync(Vec<String>, String),
+    // 求和
+    DrawOffer,
+    // 同意和棋
+    DrawAccept,
+    // 拒绝和棋
+    DrawDecline,
+    // 认输
+    Resign,
+    // 主场方告知观众对局结果, None为和棋
+    Over(Option<Side>),
+    // 聊天, 与走法使用同一连接
+    Chat(String),
+}
+
+impl Message {
+    pub fn encode(&self) -> String {
+        match self {
+            Message::Join => String::from("JOIN"),
+            Message::Watch => String::from("WATCH"),
+            Message::Start(side, time_control) => format!("START {} {}", side.code(), time_control),
+            Message::Move(seq, iccs) => format!("MOVE {} {}", seq, iccs),
+            Message::Ack(seq) => format!("ACK {}", seq),
+            Message::Sync(moves, fen) => {
+                let mut line = format!("SYNC {}", moves.len());
+                for iccs in moves {
+                    line.push(' ');
+                    line.push_str(iccs);
+                }
+                line.push(' ');
+                line.push_str(fen);
+                line
+            }
+            Message::DrawOffer => String::from("DRAW offer"),
+            Message::DrawAccept => String::from("DRAW accept"),
+            Message::DrawDecline => String::from("DRAW decline"),
+            Message::Resign => String::from("RESIGN"),
+            Message::Over(Some(side)) => format!("OVER {}", side.code()),
+            Message::Over(None) => String::from("OVER draw"),
+            Message::Chat(text) => format!("CHAT {}", text),
+        }
+    }
+
+    /// 解析一行消息, 格式错误返回None
+    pub fn decode(line: &str) -> Option<Self> {
+        // 聊天内容可以包含空格, 整行剩余部分都是内容
+        if let Some(text) = line.strip_prefix("CHAT ") {
+            return chat_text(text).map(Message::Chat);
+        }
+        let mut fields = line.split_whitespace();
+        let message = match (fields.next()?, fields.next()) {
+            ("JOIN", None) => Message::Join,
+            ("WATCH", None) => Message::Watch,
+            ("START", Some(side)) => {
+                let side = match side {
+                    "w" => Side::White,
+                    "b" => Side::Black,
+                    _ => return None,
+                };
+                let time_control: usize = fields.next()?.parse().ok()?;
+                if time_control >= TIME_CONTROLS.len() {
+                    return None;
+                }
+                Message::Start(side, time_control)
+            }
+            ("MOVE", Some(seq)) => {
+                let seq = seq.parse().ok()?;
+                match fields.next()? {
+                    iccs if is_iccs(iccs) => Message::Move(seq, iccs.to_string()),
+                    _ => return None,
+                }
+            }
+            ("ACK", Some(seq)) => Message::Ack(seq.parse().ok()?),
+            ("SYNC", Some(count)) => {
+                let count: usize = count.parse().ok()?;
+                let mut moves = Vec::new();
+                for _ in 0..count {
+                    match fields.next()? {
+                        iccs if is_iccs(iccs) => moves.push(iccs.to_string()),
+                        _ => return None,
+                    }
+                }
+                // 剩余字段为FEN
+                let fen = fields.collect::<Vec<&str>>().join(" ");
+                if fen.is_empty() {
+                    return None;
+                }
+                return Some(Message::Sync(moves, fen));
+            }
+            ("DRAW", Some("offer")) => Message::DrawOffer,
+            ("DRAW", Some("accept")) => Message::DrawAccept,
+            ("DRAW", Some("decline")) => Message::DrawDecline,
+            ("RESIGN", None) => Message::Resign,
+            ("OVER", Some("w")) => Message::Over(Some(Side::White)),
+            ("OVER", Some("b")) => Message::Over(Some(Side::Black)),
+            ("OVER", Some("draw")) => Message::Over(None),
+            _ => return None,
+        };
+        // 不允许多余的字段
+        match fields.next() {
+            Some(_) => None,
+            None => Some(message),
+        }
+    }
+}
+
+/// 整理聊天内容: 去掉控制字符与首尾空白, 超长截断, 空内容返回None
+pub fn chat_text(text: &str) -> Option<String> {
+    let text: String = text.chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+    let text: String = text.trim().chars().take(CHAT_MAX_LEN).collect();
+    match text.trim_end() {
+        "" => None,
+        text => Some(text.to_string()),
+    }
+}
+
+/// 重连后比较双方走法记录的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resync {
+    // 双方记录一致
+    Same,
+    // 本方没有收到对方最后一步
+    Behind(String),
+    // 对方没有收到本方最后一步
+    Ahead,
+    // 记录不一致, 无法同步
+    Diverged,
+}
+
+/// 双方轮流行棋, 同一前缀下最多相差一步
+pub fn resync(local: &[String], remote: &[String]) -> Resync {
+    let common = local.iter().zip(remote).take_while(|(a, b)| a == b).count();
+    if common != local.len().min(remote.len()) {
+        return Resync::Diverged;
+    }
+    match remote.len() as isize - local.len() as isize {
+        0 => Resync::Same,
+        1 => Resync::Behind(remote[local.len()].clone()),
+        -1 => Resync::Ahead,
+        _ => Resync::Diverged,
+    }
+}
+
+/// ICCS走法格式: 列a-i, 行0-9, 如 h2e2
+pub fn is_iccs(iccs: &str) -> bool {
+    let bytes = iccs.as_bytes();
+    bytes.len() == 4
+        && bytes
+            .chunks(2)
+            .all(|square| (b'a'..=b'i').contains(&square[0]) && square[1].is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        let messages = [
+            Message::Join,
+            Message::Watch,
+            Message::Start(Side::White, 0),
+            Message::Start(Side::Black, TIME_CONTROLS.len() - 1),
+            Message::Move(1, String::from("h2e2")),
+            Message::Ack(1),
+            Message::Sync(Vec::new(), String::from(crate::public::START_POS)),
+            Message::Sync(
+                vec![String::from("h2e2"), String::from("h9g7")],
+                String::from(
+                    "rnbakab1r/9/1c4nc1/p1p1p1p1p/9/9/P1P1P1P1P/1C2C4/9/RNBAKABNR w - - 1 1",
+                ),
+            ),
+            Message::DrawOffer,
+            Message::DrawAccept,
+            Message::DrawDecline,
+            Message::Resign,
+            Message::Over(Some(Side::White)),
+            Message::Over(Some(Side::Black)),
+            Message::Over(None),
+            Message::Chat(String::from("好棋")),
+            Message::Chat(String::from("再来 一局 GG")),
+        ];
+        for message in messages {
+            assert_eq!(Message::decode(&message.encode()), Some(message));
+        }
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        for line in [
+            "",
+            "MOVE",
+            "MOVE h2e2",
+            "MOVE 1 j2e2",
+            "MOVE 1 h2e",
+            "MOVE 1 h2e2 h7e7",
+            "ACK",
+            "ACK -1",
+            "SYNC 1",
+            "SYNC 1 h2e2",
+            "SYNC 2 h2e2 fen",
+            "START r 0",
+            "START b",
+            "START b 99",
+            "DRAW",
+            "RESIGN now",
+            "JOIN now",
+            "OVER",
+            "OVER tie",
+            "move h2e2",
+            "CHAT",
+            "CHAT    ",
+        ] {
+            assert_eq!(Message::decode(line), None, "{line}");
+        }
+    }
+
+    #[test]
+    fn test_chat_text() {
+        assert_eq!(chat_text("  好棋 "), Some(String::from("好棋")));
+        assert_eq!(chat_text("再来\n一局"), Some(String::from("再来 一局")));
+        assert_eq!(chat_text("\t\r\n"), None);
+        assert_eq!(chat_text(""), None);
+        let long = "将".repeat(CHAT_MAX_LEN + 10);
+        assert_eq!(chat_text(&long).unwrap().chars().count(), CHAT_MAX_LEN);
+        // 换行不会拆成两条消息
+        let line = Message::Chat(chat_text("一\n二").unwrap()).encode();
+        assert!(!line.contains('\n'));
+        assert_eq!(Message::decode("CHAT  好棋 "), Some(Message::Chat(String::from("好棋"))));
+    }
+
+    #[test]
+    fn test_resync() {
+        let moves: Vec<String> = ["h2e2", "h9g7", "h0g2"].iter().map(|m| m.to_string()).collect();
+        assert_eq!(resync(&moves, &moves), Resync::Same);
+        assert_eq!(resync(&[], &[]), Resync::Same);
+        assert_eq!(resync(&moves[..2], &moves), Resync::Behind(String::from("h0g2")));
+        assert_eq!(resync(&moves, &moves[..2]), Resync::Ahead);
+        assert_eq!(resync(&moves[..1], &moves), Resync::Diverged);
+        let other = vec![String::from("b2e2")];
+        assert_eq!(resync(&moves[..1], &other), Resync::Diverged);
+        assert_eq!(resync(&other, &moves[..2]), Resync::Diverged);
+    }
+
+    #[test]
+    fn test_is_iccs() {
+        assert!(is_iccs("a0i9"));
+        assert!(!is_iccs("a0i"));
+        assert!(!is_iccs("A0I9"));
+        assert!(!is_iccs("a0j9"));
+    }
+}
@@ -0,0 +1,438 @@
+//! 中文纵线记法, 如 炮二平五 / 马8进7
+//!
+//! 红方纵线与步数用中文数字, 黑方用阿拉伯数字; 同一纵线上的同类棋子用前中后或一二三区分,
+//! 兵卒在两条以上纵线都有多个时用位置加纵线代替棋子名称, 如 前七进一
+
+use crate::component::piece::{Kind, Side};
+use crate::public::Pos;
+
+use super::{describe, locate, resolve, Action, BroadMap, Locator, NotationError, Order};
+
+const RED_NUMBERS: [char; 10] = ['零', '一', '二', '三', '四', '五', '六', '七', '八', '九'];
+
+fn piece_name(side: Side, kind: Kind) -> char {
+    match (side, kind) {
+        (_, Kind::Rook) => '车',
+        (_, Kind::Knight) => '马',
+        (_, Kind::Cannon) => '炮',
+        (Side::White, Kind::Bishop) => '相',
+        (Side::White, Kind::Advisor) => '仕',
+        (Side::White, Kind::King) => '帅',
+        (Side::White, Kind::Pawn) => '兵',
+        (Side::Black, Kind::Bishop) => '象',
+        (Side::Black, Kind::Advisor) => '士',
+        (Side::Black, Kind::King) => '将',
+        (Side::Black, Kind::Pawn) => '卒',
+    }
+}
+
+/// 兼容繁体与各种常见写法
+fn parse_kind(name: char) -> Option<Kind> {
+    match name {
+        '车' | '車' | '俥' => Some(Kind::Rook),
+        '马' | '馬' | '傌' => Some(Kind::Knight),
+        '相' | '象' => Some(Kind::Bishop),
+        '仕' | '士' => Some(Kind::Advisor),
+        '帅' | '帥' | '将' | '將' => Some(Kind::King),
+        '炮' | '砲' | '包' => Some(Kind::Cannon),
+        '兵' | '卒' => Some(Kind::Pawn),
+        _ => None,
+    }
+}
+
+fn number(side: Side, n: usize) -> char {
+    match side {
+        Side::White => RED_NUMBERS[n],
+        Side::Black => char::from_digit(n as u32, 10).unwrap(),
+    }
+}
+
+/// 双方的数字都可以用中文、阿拉伯或全角数字
+fn parse_number(c: char) -> Option<usize> {
+    if let Some(n) = RED_NUMBERS.iter().position(|&number| number == c) {
+        return Some(n).filter(|n| *n > 0);
+    }
+    match c {
+        '1'..='9' => c.to_digit(10).map(|n| n as usize),
+        '１'..='９' => Some(c as usize - '０' as usize),
+        _ => None,
+    }
+}
+
+fn order_name(order: Order) -> char {
+    match order {
+        Order::Front => '前',
+        Order::Middle => '中',
+        Order::Rear => '后',
+        Order::Nth(n) => RED_NUMBERS[n],
+    }
+}
+
+fn parse_order(c: char) -> Option<Order> {
+    match c {
+        '前' => Some(Order::Front),
+        '中' => Some(Order::Middle),
+        '后' | '後' => Some(Order::Rear),
+        _ => match RED_NUMBERS.iter().position(|&number| number == c) {
+            Some(n @ 1..=5) => Some(Order::Nth(n)),
+            _ => None,
+        },
+    }
+}
+
+fn action_name(action: Action) -> char {
+    match action {
+        Action::Forward => '进',
+        Action::Backward => '退',
+        Action::Traverse => '平',
+    }
+}
+
+fn parse_action(c: char) -> Option<Action> {
+    match c {
+        '进' | '進' => Some(Action::Forward),
+        '退' => Some(Action::Backward),
+        '平' => Some(Action::Traverse),
+        _ => None,
+    }
+}
+
+/// 把走法编码为中文纵线记法
+pub fn encode(broad_map: &BroadMap, src: Pos, dst: Pos) -> Result<String, NotationError> {
+    let piece = broad_map[src.row][src.col].ok_or(NotationError::NoPiece)?;
+    let side = piece.side;
+    let mut text = String::new();
+    match locate(broad_map, src)? {
+        Locator::File(file) => {
+            text.push(piece_name(side, piece.kind));
+            text.push(number(side, file));
+        }
+        Locator::Order(order) => {
+            text.push(order_name(order));
+            text.push(piece_name(side, piece.kind));
+        }
+        Locator::OrderFile(order, file) => {
+            text.push(order_name(order));
+            text.push(number(side, file));
+        }
+    }
+    let (action, n) = describe(piece.kind, side, src, dst);
+    text.push(action_name(action));
+    text.push(number(side, n));
+    Ok(text)
+}
+
+/// 解析行棋方的中文纵线记法, 返回起点与落点; 只检查棋子走法的形状, 是否合法由引擎判断
+pub fn decode(broad_map: &BroadMap, side: Side, text: &str) -> Result<(Pos, Pos), NotationError> {
+    let format = || NotationError::Format(text.to_string());
+    let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if chars.len() != 4 {
+        return Err(format());
+    }
+    let action = parse_action(chars[2]).ok_or_else(format)?;
+    let n = parse_number(chars[3]).ok_or_else(format)?;
+    let (kind, locator) = match parse_kind(chars[0]) {
+        Some(kind) => (kind, Locator::File(parse_number(chars[1]).ok_or_else(format)?)),
+        None => {
+            let order = parse_order(chars[0]).ok_or_else(format)?;
+            match parse_kind(chars[1]) {
+                Some(kind) => (kind, Locator::Order(order)),
+                // 多条纵线都有多个兵卒时用纵线代替名称
+                None => {
+                    let file = parse_number(chars[1]).ok_or_else(format)?;
+                    (Kind::Pawn, Locator::OrderFile(order, file))
+                }
+            }
+        }
+    };
+    resolve(broad_map, side, kind, locator, action, n, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{destination, testing::*};
+    use super::*;
+
+    /// 编码与解码互为逆运算
+    fn check(placement: &str, side: Side, cases: &[(&str, &str)]) {
+        let broad_map = board(placement);
+        for (mv, text) in cases {
+            let (src, dst) = parse_iccs(mv);
+            assert_eq!(encode(&broad_map, src, dst).unwrap(), *text, "{mv}");
+            assert_eq!(iccs(decode(&broad_map, side, text).unwrap()), *mv, "{text}");
+        }
+    }
+
+    #[test]
+    fn test_opening() {
+        check(
+            START_BOARD,
+            Side::White,
+            &[
+                ("h2e2", "炮二平五"),
+                ("b2e2", "炮八平五"),
+                ("h2h9", "炮二进七"),
+                ("h0g2", "马二进三"),
+                ("b0c2", "马八进七"),
+                ("i0i2", "车一进二"),
+                ("a0a1", "车九进一"),
+                ("g0e2", "相三进五"),
+                ("c0e2", "相七进五"),
+                ("f0e1", "仕四进五"),
+                ("d0e1", "仕六进五"),
+                ("e0e1", "帅五进一"),
+                ("c3c4", "兵七进一"),
+                ("g3g4", "兵三进一"),
+            ],
+        );
+        check(
+            START_BOARD,
+            Side::Black,
+            &[
+                ("h7e7", "炮8平5"),
+                ("b7e7", "炮2平5"),
+                ("h9g7", "马8进7"),
+                ("b9c7", "马2进3"),
+                ("i9i7", "车9进2"),
+                ("c9e7", "象3进5"),
+                ("d9e8", "士4进5"),
+                ("e9e8", "将5进1"),
+                ("c6c5", "卒3进1"),
+                ("g6g5", "卒7进1"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_backward() {
+        // 车马炮都在对方阵地
+        let placement = "4k4/9/2N3R2/9/6C2/9/9/9/9/4K4";
+        check(
+            placement,
+            Side::White,
+            &[
+                ("c7d9", "马七进六"),
+                ("c7b5", "马七退八"),
+                ("c7e6", "马七退五"),
+                ("g7g6", "车三退一"),
+                ("g7g9", "车三进二"),
+                ("g7d7", "车三平六"),
+                ("g5g1", "炮三退四"),
+                ("g5a5", "炮三平九"),
+            ],
+        );
+        let placement = "4k4/9/9/9/9/2c6/9/2n3r2/9/4K4";
+        check(
+            placement,
+            Side::Black,
+            &[
+                ("c2d0", "马3进4"),
+                ("c2b4", "马3退2"),
+                ("g2g9", "车7退7"),
+                ("g2g0", "车7进2"),
+                ("c4c9", "炮3退5"),
+                ("c4i4", "炮3平9"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_tandem() {
+        // 红方两车同线, 黑方两炮同线
+        let placement = "3k5/9/4c4/9/4c4/1R7/9/1R7/9/4K4";
+        check(
+            placement,
+            Side::White,
+            &[
+                ("b4b5", "前车进一"),
+                ("b4b3", "前车退一"),
+                ("b2b3", "后车进一"),
+                ("b2e2", "后车平五"),
+                ("b4e4", "前车平五"),
+            ],
+        );
+        check(
+            placement,
+            Side::Black,
+            &[
+                ("e5e3", "前炮进2"),
+                ("e5d5", "前炮平4"),
+                ("e7e8", "后炮退1"),
+                ("e7e6", "后炮进1"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_advisor_bishop_tandem() {
+        // 仕相同线不用前后, 由方向区分
+        let placement = "3k5/9/9/9/9/9/9/2BA5/9/3AK1B2";
+        check(
+            placement,
+            Side::White,
+            &[
+                ("d0e1", "仕六进五"),
+                ("d2e1", "仕六退五"),
+                ("c2a0", "相七退九"),
+                ("c2a4", "相七进九"),
+                ("g0e2", "相三进五"),
+            ],
+        );
+        let placement = "3ak1b2/9/2ba5/9/9/9/9/9/9/4K4";
+        check(
+            placement,
+            Side::Black,
+            &[("d9e8", "士4进5"), ("d7e8", "士4退5"), ("c7a5", "象3进1"), ("c7a9", "象3退1")],
+        );
+    }
+
+    #[test]
+    fn test_three_pawns() {
+        let placement = "3k5/4P4/4P4/4P4/9/9/9/9/9/4K4";
+        check(
+            placement,
+            Side::White,
+            &[("e8d8", "前兵平六"), ("e7f7", "中兵平四"), ("e6d6", "后兵平六")],
+        );
+        let placement = "4k4/9/9/9/9/4p4/4p4/4p4/9/3K5";
+        check(
+            placement,
+            Side::Black,
+            &[("e2e1", "前卒进1"), ("e3d3", "中卒平4"), ("e4f4", "后卒平6")],
+        );
+    }
+
+    #[test]
+    fn test_many_pawns() {
+        // 同一纵线四个与五个兵卒, 由前到后用一二三四五
+        let placement = "3k5/4P4/4P4/4P4/4P4/9/9/9/9/5K3";
+        check(
+            placement,
+            Side::White,
+            &[
+                ("e8d8", "一兵平六"),
+                ("e7d7", "二兵平六"),
+                ("e6f6", "三兵平四"),
+                ("e5f5", "四兵平四"),
+            ],
+        );
+        let placement = "4k4/9/9/9/9/4p4/4p4/4p4/4p4/4p1K2";
+        check(
+            placement,
+            Side::Black,
+            &[
+                ("e0d0", "一卒平4"),
+                ("e1d1", "二卒平4"),
+                ("e2f2", "三卒平6"),
+                ("e3f3", "四卒平6"),
+                ("e4d4", "五卒平4"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_tandem_pawns_on_two_files() {
+        // 两条纵线都有多个兵卒时用位置加纵线
+        let placement = "3k5/9/2P3P2/2P3P2/9/9/9/9/9/4K4";
+        check(
+            placement,
+            Side::White,
+            &[
+                ("c7c8", "前七进一"),
+                ("c6b6", "后七平八"),
+                ("g7g8", "前三进一"),
+                ("g6h6", "后三平二"),
+            ],
+        );
+        let placement = "4k4/9/9/9/9/9/2p1p4/2p1p1p2/6p2/3K2p2";
+        check(
+            placement,
+            Side::Black,
+            &[
+                ("c2c1", "前3进1"),
+                ("c3b3", "后3平2"),
+                ("e3d3", "后5平4"),
+                ("g0f0", "前7平6"),
+                ("g1f1", "中7平6"),
+                ("g2f2", "后7平6"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_decode_variants() {
+        let broad_map = board(START_BOARD);
+        for text in ["炮２平５", "砲二平五", " 炮 二 平 五 ", "炮2平5"] {
+            assert_eq!(iccs(decode(&broad_map, Side::White, text).unwrap()), "h2e2");
+        }
+        assert_eq!(iccs(decode(&broad_map, Side::White, "俥一進一").unwrap()), "i0i1");
+        assert_eq!(iccs(decode(&broad_map, Side::Black, "馬8進7").unwrap()), "h9g7");
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let broad_map = board(START_BOARD);
+        let format = |text: &str| Err(NotationError::Format(text.to_string()));
+        for text in ["", "炮二平", "炮二平五五", "炮二走五", "兔二平五", "炮零平五", "炮二平十"]
+        {
+            assert_eq!(decode(&broad_map, Side::White, text), format(text), "{text}");
+        }
+        let not_found = |text: &str| Err(NotationError::NotFound(text.to_string()));
+        for text in ["车五进一", "前车进一", "后兵进一", "马一进二"] {
+            assert_eq!(decode(&broad_map, Side::White, text), not_found(text), "{text}");
+        }
+        let invalid = |text: &str| Err(NotationError::Invalid(text.to_string()));
+        for text in ["马二平三", "马二进五", "车一退一", "相三进九", "仕四进四", "帅五平四"]
+        {
+            assert_eq!(decode(&broad_map, Side::White, text), invalid(text), "{text}");
+        }
+
+        // 两条纵线都有多个兵时只写前兵不能确定
+        let broad_map = board("3k5/9/2P3P2/2P3P2/9/9/9/9/9/4K4");
+        let text = "前兵进一";
+        let ambiguous = Err(NotationError::Ambiguous(text.to_string()));
+        assert_eq!(decode(&broad_map, Side::White, text), ambiguous);
+        assert_eq!(encode(&broad_map, Pos::new(0, 0), Pos::new(1, 0)), Err(NotationError::NoPiece));
+    }
+
+    /// 穷举各局面中每个棋子所有形状正确的走法, 编码后解码得到原走法且编码互不相同
+    #[test]
+    fn test_exhaustive_round_trip() {
+        let placements = [
+            START_BOARD,
+            "r1bakab1r/9/1cn1c1n2/p1p1p1p1p/9/2P6/P3P1P1P/1CN1C1N2/9/R1BAKAB1R",
+            "3k5/4P4/4P4/4P4/4P4/9/9/9/9/5K3",
+            "3k5/9/2P3P2/2P3P2/9/9/9/9/9/4K4",
+            "4k4/9/9/9/9/9/2p1p4/2p1p1p2/6p2/3K2p2",
+            "3k5/9/4c4/9/4c4/1R7/9/1R7/9/4K4",
+            "3ak1b2/9/2ba5/9/9/9/9/2BA5/9/3AK1B2",
+            "2R6/3k5/R3P4/2NP5/3P5/9/4p4/3n5/3pK4/4r4",
+        ];
+        for placement in placements {
+            let broad_map = board(placement);
+            for side in [Side::White, Side::Black] {
+                let mut seen = Vec::new();
+                for piece in broad_map.iter().flatten().flatten().filter(|p| p.side == side) {
+                    let src = Pos::new(piece.row, piece.col);
+                    for row in 0..10 {
+                        for col in 0..9 {
+                            let dst = Pos::new(row, col);
+                            let (action, n) = describe(piece.kind, side, src, dst);
+                            if destination(&broad_map, side, piece.kind, src, action, n)
+                                != Some(dst)
+                            {
+                                continue;
+                            }
+                            let text = encode(&broad_map, src, dst).unwrap();
+                            let decoded = decode(&broad_map, side, &text);
+                            assert_eq!(decoded, Ok((src, dst)), "{placement} {text}");
+                            assert!(!seen.contains(&text), "{placement} {text}");
+                            seen.push(text);
+                        }
+                    }
+                }
+                assert!(!seen.is_empty());
+            }
+        }
+    }
+}
@@ -0,0 +1,383 @@
+//! 走法记法: ICCS、中文纵线记法、WXF记法与引擎走法(棋盘坐标)之间的转换
+
+pub mod chinese;
+pub mod wxf;
+
+use std::fmt;
+
+use chessai::position::pos2iccs;
+
+use crate::component::piece::{Kind, Piece, Side};
+use crate::public::Pos;
+
+/// 棋盘地图, 与 `game::Data::broad_map` 相同
+pub type BroadMap = [[Option<Piece>; 9]; 10];
+
+/// 记法转换错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotationError {
+    // 起点没有棋子
+    NoPiece,
+    // 记法格式错误
+    Format(String),
+    // 找不到记法所指的棋子
+    NotFound(String),
+    // 记法可以指多个棋子
+    Ambiguous(String),
+    // 落点越界或不符合棋子走法
+    Invalid(String),
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotationError::NoPiece => write!(f, "起点没有棋子"),
+            NotationError::Format(text) => write!(f, "记法格式错误: {text}"),
+            NotationError::NotFound(text) => write!(f, "找不到要走的棋子: {text}"),
+            NotationError::Ambiguous(text) => write!(f, "记法不能确定棋子: {text}"),
+            NotationError::Invalid(text) => write!(f, "走法不合法: {text}"),
+        }
+    }
+}
+
+/// 棋谱显示的记法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Notation {
+    // 中文纵线记法, 如 炮二平五
+    #[default]
+    Chinese,
+    // WXF记法, 如 C2=5
+    Wxf,
+    // ICCS坐标记法, 如 h2e2
+    Iccs,
+}
+
+impl Notation {
+    pub fn name(&self) -> &str {
+        match self {
+            Notation::Chinese => "中文",
+            Notation::Wxf => "WXF",
+            Notation::Iccs => "ICCS",
+        }
+    }
+
+    /// 依次切换
+    pub fn next(&self) -> Self {
+        match self {
+            Notation::Chinese => Notation::Wxf,
+            Notation::Wxf => Notation::Iccs,
+            Notation::Iccs => Notation::Chinese,
+        }
+    }
+
+    pub fn encode(
+        &self,
+        broad_map: &BroadMap,
+        src: Pos,
+        dst: Pos,
+    ) -> Result<String, NotationError> {
+        match self {
+            Notation::Chinese => chinese::encode(broad_map, src, dst),
+            Notation::Wxf => wxf::encode(broad_map, src, dst),
+            Notation::Iccs => Ok(pos2iccs(src.row, src.col, dst.row, dst.col)),
+        }
+    }
+}
+
+/// 行棋方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    // 进
+    Forward,
+    // 退
+    Backward,
+    // 平
+    Traverse,
+}
+
+/// 同一纵线上同类棋子由前到后的位置: 两个用前后, 三个用前中后, 更多时用序号
+/// 序号也可以用于三个棋子, WXF记法三个以上都用序号
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    Front,
+    Middle,
+    Rear,
+    // 由前到后第几个, 从1起
+    Nth(usize),
+}
+
+impl Order {
+    pub fn new(index: usize, count: usize) -> Self {
+        match (index, count) {
+            (0, 2..=3) => Order::Front,
+            (1, 3) => Order::Middle,
+            (_, 2..=3) => Order::Rear,
+            _ => Order::Nth(index + 1),
+        }
+    }
+
+    /// 纵线上有count个同类棋子时对应的序号, 从0起
+    pub fn index(&self, count: usize) -> Option<usize> {
+        match (self, count) {
+            (Order::Front, 2..=3) => Some(0),
+            (Order::Middle, 3) => Some(1),
+            (Order::Rear, 2..=3) => Some(count - 1),
+            (Order::Nth(n), 3..) if (1..=count).contains(n) => Some(n - 1),
+            _ => None,
+        }
+    }
+}
+
+/// 棋子在同类棋子中的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locator {
+    // 纵线上只有一个同类棋子, 用纵线序号区分
+    File(usize),
+    // 同一纵线上有多个同类棋子, 用位置区分
+    Order(Order),
+    // 兵在多条纵线上都有多个, 同时用位置与纵线序号区分
+    OrderFile(Order, usize),
+}
+
+/// 纵线序号: 各方从本方右手起为1到9
+pub fn col_to_file(side: Side, col: usize) -> usize {
+    match side {
+        Side::White => 9 - col,
+        Side::Black => col + 1,
+    }
+}
+
+pub fn file_to_col(side: Side, file: usize) -> Option<usize> {
+    if !(1..=9).contains(&file) {
+        return None;
+    }
+    Some(match side {
+        Side::White => 9 - file,
+        Side::Black => file - 1,
+    })
+}
+
+/// 斜走的棋子记落点纵线, 直走的棋子记步数
+fn is_diagonal(kind: Kind) -> bool {
+    matches!(kind, Kind::Knight | Kind::Bishop | Kind::Advisor)
+}
+
+/// 一方某条纵线上的同类棋子, 由前到后排列
+pub fn tandem(broad_map: &BroadMap, side: Side, kind: Kind, col: usize) -> Vec<Pos> {
+    let mut pieces: Vec<Pos> = (0..10)
+        .filter(|row| {
+            broad_map[*row][col].is_some_and(|piece| piece.side == side && piece.kind == kind)
+        })
+        .map(|row| Pos::new(row, col))
+        .collect();
+    if side == Side::White {
+        pieces.reverse();
+    }
+    pieces
+}
+
+/// 确定起点棋子的位置: 仕相同线时能进的在后、能退的在前, 由方向即可区分, 不用前后
+pub fn locate(broad_map: &BroadMap, src: Pos) -> Result<Locator, NotationError> {
+    let piece = broad_map[src.row][src.col].ok_or(NotationError::NoPiece)?;
+    let file = col_to_file(piece.side, src.col);
+    let pieces = tandem(broad_map, piece.side, piece.kind, src.col);
+    if pieces.len() < 2 || matches!(piece.kind, Kind::Advisor | Kind::Bishop) {
+        return Ok(Locator::File(file));
+    }
+    let index = pieces.iter().position(|pos| *pos == src).unwrap();
+    let order = Order::new(index, pieces.len());
+    let tandem_files = (0..9)
+        .filter(|col| tandem(broad_map, piece.side, piece.kind, *col).len() > 1)
+        .count();
+    match (piece.kind, tandem_files) {
+        (Kind::Pawn, 2..) => Ok(Locator::OrderFile(order, file)),
+        _ => Ok(Locator::Order(order)),
+    }
+}
+
+/// 按位置找出符合的棋子
+pub fn find(broad_map: &BroadMap, side: Side, kind: Kind, locator: Locator) -> Vec<Pos> {
+    match locator {
+        Locator::File(file) => match file_to_col(side, file) {
+            Some(col) => tandem(broad_map, side, kind, col),
+            None => Vec::new(),
+        },
+        Locator::Order(order) => (0..9)
+            .map(|col| tandem(broad_map, side, kind, col))
+            .filter_map(|pieces| order.index(pieces.len()).map(|index| pieces[index]))
+            .collect(),
+        Locator::OrderFile(order, file) => match file_to_col(side, file) {
+            Some(col) => {
+                let pieces = tandem(broad_map, side, kind, col);
+                order.index(pieces.len()).map(|index| pieces[index]).into_iter().collect()
+            }
+            None => Vec::new(),
+        },
+    }
+}
+
+/// 分解走法为方向与数字, 数字为落点纵线序号或步数
+pub fn describe(kind: Kind, side: Side, src: Pos, dst: Pos) -> (Action, usize) {
+    if src.row == dst.row {
+        return (Action::Traverse, col_to_file(side, dst.col));
+    }
+    let action = match (dst.row > src.row) == (side == Side::White) {
+        true => Action::Forward,
+        false => Action::Backward,
+    };
+    match is_diagonal(kind) {
+        true => (action, col_to_file(side, dst.col)),
+        false => (action, src.row.abs_diff(dst.row)),
+    }
+}
+
+/// 由方向与数字计算落点, 只检查棋子走法的形状与落点范围, 是否合法由引擎判断
+pub fn destination(
+    broad_map: &BroadMap,
+    side: Side,
+    kind: Kind,
+    src: Pos,
+    action: Action,
+    number: usize,
+) -> Option<Pos> {
+    let forward: isize = match side {
+        Side::White => 1,
+        Side::Black => -1,
+    };
+    let (row, col) = match (action, is_diagonal(kind)) {
+        (Action::Traverse, true) => return None,
+        (Action::Traverse, false) => (src.row as isize, file_to_col(side, number)? as isize),
+        (_, false) => {
+            let step = match action {
+                Action::Forward => forward,
+                _ => -forward,
+            };
+            (src.row as isize + step * number as isize, src.col as isize)
+        }
+        (_, true) => {
+            let col = file_to_col(side, number)?;
+            let dc = col.abs_diff(src.col);
+            let dr = match (kind, dc) {
+                (Kind::Knight, 1) => 2,
+                (Kind::Knight, 2) => 1,
+                (Kind::Advisor, 1) => 1,
+                (Kind::Bishop, 2) => 2,
+                _ => return None,
+            };
+            let step = match action {
+                Action::Forward => forward,
+                _ => -forward,
+            };
+            (src.row as isize + step * dr, col as isize)
+        }
+    };
+    if !(0..10).contains(&row) || !(0..9).contains(&col) {
+        return None;
+    }
+    let dst = Pos::new(row as usize, col as usize);
+    if dst == src
+        || broad_map[dst.row][dst.col].is_some_and(|piece| piece.side == side)
+        || !in_area(side, kind, dst)
+    {
+        return None;
+    }
+    Some(dst)
+}
+
+/// 帅仕只能在九宫, 相不能过河
+fn in_area(side: Side, kind: Kind, pos: Pos) -> bool {
+    let home = match side {
+        Side::White => pos.row <= 4,
+        Side::Black => pos.row >= 5,
+    };
+    let palace = (3..=5).contains(&pos.col)
+        && match side {
+            Side::White => pos.row <= 2,
+            Side::Black => pos.row >= 7,
+        };
+    match kind {
+        Kind::King | Kind::Advisor => palace,
+        Kind::Bishop => home,
+        _ => true,
+    }
+}
+
+/// 由位置、方向与数字确定走法, 多个棋子符合时用落点排除
+pub fn resolve(
+    broad_map: &BroadMap,
+    side: Side,
+    kind: Kind,
+    locator: Locator,
+    action: Action,
+    number: usize,
+    text: &str,
+) -> Result<(Pos, Pos), NotationError> {
+    let candidates = find(broad_map, side, kind, locator);
+    if candidates.is_empty() {
+        return Err(NotationError::NotFound(text.to_string()));
+    }
+    let moves: Vec<(Pos, Pos)> = candidates
+        .into_iter()
+        .filter_map(|src| {
+            destination(broad_map, side, kind, src, action, number).map(|dst| (src, dst))
+        })
+        .collect();
+    match moves[..] {
+        [] => Err(NotationError::Invalid(text.to_string())),
+        [mv] => Ok(mv),
+        _ => Err(NotationError::Ambiguous(text.to_string())),
+    }
+}
+
+/// 各记法测试共用的局面与走法辅助
+#[cfg(test)]
+pub mod testing {
+    use super::BroadMap;
+    use crate::component::piece::{Kind, Piece};
+    use crate::public::Pos;
+
+    pub const START_BOARD: &str = "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR";
+
+    /// 由FEN的棋盘部分生成棋盘地图, 第一行为黑方底线
+    pub fn board(placement: &str) -> BroadMap {
+        let mut broad_map: BroadMap = Default::default();
+        for (i, rank) in placement.split('/').enumerate() {
+            let row = 9 - i;
+            let mut col = 0;
+            for c in rank.chars() {
+                if let Some(n) = c.to_digit(10) {
+                    col += n as usize;
+                    continue;
+                }
+                let kind = match c.to_ascii_lowercase() {
+                    'r' => Kind::Rook,
+                    'n' => Kind::Knight,
+                    'b' => Kind::Bishop,
+                    'a' => Kind::Advisor,
+                    'k' => Kind::King,
+                    'c' => Kind::Cannon,
+                    'p' => Kind::Pawn,
+                    _ => panic!("{c}"),
+                };
+                broad_map[row][col] = Some(match c.is_ascii_uppercase() {
+                    true => Piece::white(kind, row, col),
+                    false => Piece::black(kind, row, col),
+                });
+                col += 1;
+            }
+        }
+        broad_map
+    }
+
+    pub fn iccs(mv: (Pos, Pos)) -> String {
+        let (src, dst) = mv;
+        let square = |pos: Pos| format!("{}{}", (b'a' + pos.col as u8) as char, pos.row);
+        format!("{}{}", square(src), square(dst))
+    }
+
+    pub fn parse_iccs(iccs: &str) -> (Pos, Pos) {
+        let b = iccs.as_bytes();
+        let pos = |col: u8, row: u8| Pos::new((row - b'0') as usize, (col - b'a') as usize);
+        (pos(b[0], b[1]), pos(b[2], b[3]))
+    }
+}
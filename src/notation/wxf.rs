@@ -0,0 +1,405 @@
+//! WXF记法, 如 C2=5 / H8+7
+//!
+//! 双方纵线与步数都用阿拉伯数字, 从本方右手起为1到9; 进用 +, 退用 -, 平用 = (也接受 .)。
+//! 同一纵线上两个同类棋子时在名称前加 + 或 - 表示前后, 如 +R+1;
+//! 三个以上兵卒用由前到后的序号, 如 2P=4; 兵卒在两条以上纵线都有多个时用纵线代替名称, 如 +7+1
+
+use crate::component::piece::{Kind, Side};
+use crate::public::Pos;
+
+use super::{describe, locate, resolve, tandem, Action, BroadMap, Locator, NotationError, Order};
+
+fn piece_letter(kind: Kind) -> char {
+    match kind {
+        Kind::Rook => 'R',
+        Kind::Knight => 'H',
+        Kind::Bishop => 'E',
+        Kind::Advisor => 'A',
+        Kind::King => 'K',
+        Kind::Cannon => 'C',
+        Kind::Pawn => 'P',
+    }
+}
+
+/// 兼容小写以及 N(马) 与 B(相) 的写法
+fn parse_kind(letter: char) -> Option<Kind> {
+    match letter.to_ascii_uppercase() {
+        'R' => Some(Kind::Rook),
+        'H' | 'N' => Some(Kind::Knight),
+        'E' | 'B' => Some(Kind::Bishop),
+        'A' => Some(Kind::Advisor),
+        'K' => Some(Kind::King),
+        'C' => Some(Kind::Cannon),
+        'P' => Some(Kind::Pawn),
+        _ => None,
+    }
+}
+
+fn number(n: usize) -> char {
+    char::from_digit(n as u32, 10).unwrap()
+}
+
+fn parse_number(c: char) -> Option<usize> {
+    match c {
+        '1'..='9' => c.to_digit(10).map(|n| n as usize),
+        _ => None,
+    }
+}
+
+/// 两个同类棋子用 +/-, 三个以上用序号
+fn order_mark(index: usize, count: usize) -> char {
+    match (index, count) {
+        (0, 2) => '+',
+        (_, 2) => '-',
+        _ => number(index + 1),
+    }
+}
+
+fn parse_order(c: char) -> Option<Order> {
+    match c {
+        '+' => Some(Order::Front),
+        '-' => Some(Order::Rear),
+        _ => parse_number(c).filter(|n| *n <= 5).map(Order::Nth),
+    }
+}
+
+fn action_mark(action: Action) -> char {
+    match action {
+        Action::Forward => '+',
+        Action::Backward => '-',
+        Action::Traverse => '=',
+    }
+}
+
+fn parse_action(c: char) -> Option<Action> {
+    match c {
+        '+' => Some(Action::Forward),
+        '-' => Some(Action::Backward),
+        '=' | '.' => Some(Action::Traverse),
+        _ => None,
+    }
+}
+
+/// 把走法编码为WXF记法
+pub fn encode(broad_map: &BroadMap, src: Pos, dst: Pos) -> Result<String, NotationError> {
+    let piece = broad_map[src.row][src.col].ok_or(NotationError::NoPiece)?;
+    let side = piece.side;
+    let pieces = tandem(broad_map, side, piece.kind, src.col);
+    let order = || {
+        let index = pieces.iter().position(|pos| *pos == src).unwrap();
+        order_mark(index, pieces.len())
+    };
+    let mut text = String::new();
+    match locate(broad_map, src)? {
+        Locator::File(file) => {
+            text.push(piece_letter(piece.kind));
+            text.push(number(file));
+        }
+        Locator::Order(_) => {
+            text.push(order());
+            text.push(piece_letter(piece.kind));
+        }
+        Locator::OrderFile(_, file) => {
+            text.push(order());
+            text.push(number(file));
+        }
+    }
+    let (action, n) = describe(piece.kind, side, src, dst);
+    text.push(action_mark(action));
+    text.push(number(n));
+    Ok(text)
+}
+
+/// 解析行棋方的WXF记法, 返回起点与落点; 只检查棋子走法的形状, 是否合法由引擎判断
+pub fn decode(broad_map: &BroadMap, side: Side, text: &str) -> Result<(Pos, Pos), NotationError> {
+    let format = || NotationError::Format(text.to_string());
+    let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if chars.len() != 4 {
+        return Err(format());
+    }
+    let action = parse_action(chars[2]).ok_or_else(format)?;
+    let n = parse_number(chars[3]).ok_or_else(format)?;
+    let (kind, locator) = match parse_kind(chars[0]) {
+        Some(kind) => (kind, Locator::File(parse_number(chars[1]).ok_or_else(format)?)),
+        None => {
+            let order = parse_order(chars[0]).ok_or_else(format)?;
+            match parse_kind(chars[1]) {
+                Some(kind) => (kind, Locator::Order(order)),
+                // 多条纵线都有多个兵卒时用纵线代替名称
+                None => {
+                    let file = parse_number(chars[1]).ok_or_else(format)?;
+                    (Kind::Pawn, Locator::OrderFile(order, file))
+                }
+            }
+        }
+    };
+    resolve(broad_map, side, kind, locator, action, n, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{destination, testing::*};
+    use super::*;
+
+    /// 编码与解码互为逆运算
+    fn check(placement: &str, side: Side, cases: &[(&str, &str)]) {
+        let broad_map = board(placement);
+        for (mv, text) in cases {
+            let (src, dst) = parse_iccs(mv);
+            assert_eq!(encode(&broad_map, src, dst).unwrap(), *text, "{mv}");
+            assert_eq!(iccs(decode(&broad_map, side, text).unwrap()), *mv, "{text}");
+        }
+    }
+
+    #[test]
+    fn test_opening() {
+        check(
+            START_BOARD,
+            Side::White,
+            &[
+                ("h2e2", "C2=5"),
+                ("b2e2", "C8=5"),
+                ("h2h9", "C2+7"),
+                ("h0g2", "H2+3"),
+                ("b0c2", "H8+7"),
+                ("i0i2", "R1+2"),
+                ("a0a1", "R9+1"),
+                ("g0e2", "E3+5"),
+                ("c0e2", "E7+5"),
+                ("f0e1", "A4+5"),
+                ("d0e1", "A6+5"),
+                ("e0e1", "K5+1"),
+                ("c3c4", "P7+1"),
+                ("g3g4", "P3+1"),
+            ],
+        );
+        check(
+            START_BOARD,
+            Side::Black,
+            &[
+                ("h7e7", "C8=5"),
+                ("b7e7", "C2=5"),
+                ("h9g7", "H8+7"),
+                ("b9c7", "H2+3"),
+                ("i9i7", "R9+2"),
+                ("c9e7", "E3+5"),
+                ("d9e8", "A4+5"),
+                ("e9e8", "K5+1"),
+                ("c6c5", "P3+1"),
+                ("g6g5", "P7+1"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_backward() {
+        let placement = "4k4/9/2N3R2/9/6C2/9/9/9/9/4K4";
+        check(
+            placement,
+            Side::White,
+            &[
+                ("c7d9", "H7+6"),
+                ("c7b5", "H7-8"),
+                ("c7e6", "H7-5"),
+                ("g7g6", "R3-1"),
+                ("g7g9", "R3+2"),
+                ("g7d7", "R3=6"),
+                ("g5g1", "C3-4"),
+                ("g5a5", "C3=9"),
+            ],
+        );
+        let placement = "4k4/9/9/9/9/2c6/9/2n3r2/9/4K4";
+        check(
+            placement,
+            Side::Black,
+            &[
+                ("c2d0", "H3+4"),
+                ("c2b4", "H3-2"),
+                ("g2g9", "R7-7"),
+                ("g2g0", "R7+2"),
+                ("c4c9", "C3-5"),
+                ("c4i4", "C3=9"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_tandem() {
+        // 红方两车同线, 黑方两炮同线
+        let placement = "3k5/9/4c4/9/4c4/1R7/9/1R7/9/4K4";
+        check(
+            placement,
+            Side::White,
+            &[
+                ("b4b5", "+R+1"),
+                ("b4b3", "+R-1"),
+                ("b2b3", "-R+1"),
+                ("b2e2", "-R=5"),
+                ("b4e4", "+R=5"),
+            ],
+        );
+        check(
+            placement,
+            Side::Black,
+            &[("e5e3", "+C+2"), ("e5d5", "+C=4"), ("e7e8", "-C-1"), ("e7e6", "-C+1")],
+        );
+    }
+
+    #[test]
+    fn test_advisor_bishop_tandem() {
+        // 仕相同线不加 +/-, 由方向区分
+        let placement = "3k5/9/9/9/9/9/9/2BA5/9/3AK1B2";
+        check(
+            placement,
+            Side::White,
+            &[
+                ("d0e1", "A6+5"),
+                ("d2e1", "A6-5"),
+                ("c2a0", "E7-9"),
+                ("c2a4", "E7+9"),
+                ("g0e2", "E3+5"),
+            ],
+        );
+        let placement = "3ak1b2/9/2ba5/9/9/9/9/9/9/4K4";
+        check(
+            placement,
+            Side::Black,
+            &[("d9e8", "A4+5"), ("d7e8", "A4-5"), ("c7a5", "E3+1"), ("c7a9", "E3-1")],
+        );
+    }
+
+    #[test]
+    fn test_three_pawns() {
+        let placement = "3k5/4P4/4P4/4P4/9/9/9/9/9/4K4";
+        check(placement, Side::White, &[("e8d8", "1P=6"), ("e7f7", "2P=4"), ("e6d6", "3P=6")]);
+        // 也接受 +/- 表示最前与最后
+        let broad_map = board(placement);
+        assert_eq!(iccs(decode(&broad_map, Side::White, "+P=6").unwrap()), "e8d8");
+        assert_eq!(iccs(decode(&broad_map, Side::White, "-P=6").unwrap()), "e6d6");
+
+        let placement = "4k4/9/9/9/9/4p4/4p4/4p4/9/3K5";
+        check(placement, Side::Black, &[("e2e1", "1P+1"), ("e3d3", "2P=4"), ("e4f4", "3P=6")]);
+    }
+
+    #[test]
+    fn test_many_pawns() {
+        let placement = "3k5/4P4/4P4/4P4/4P4/9/9/9/9/5K3";
+        check(
+            placement,
+            Side::White,
+            &[("e8d8", "1P=6"), ("e7d7", "2P=6"), ("e6f6", "3P=4"), ("e5f5", "4P=4")],
+        );
+        let placement = "4k4/9/9/9/9/4p4/4p4/4p4/4p4/4p1K2";
+        check(
+            placement,
+            Side::Black,
+            &[
+                ("e0d0", "1P=4"),
+                ("e1d1", "2P=4"),
+                ("e2f2", "3P=6"),
+                ("e3f3", "4P=6"),
+                ("e4d4", "5P=4"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_tandem_pawns_on_two_files() {
+        // 两条纵线都有多个兵卒时用位置加纵线
+        let placement = "3k5/9/2P3P2/2P3P2/9/9/9/9/9/4K4";
+        check(
+            placement,
+            Side::White,
+            &[("c7c8", "+7+1"), ("c6b6", "-7=8"), ("g7g8", "+3+1"), ("g6h6", "-3=2")],
+        );
+        let placement = "4k4/9/9/9/9/9/2p1p4/2p1p1p2/6p2/3K2p2";
+        check(
+            placement,
+            Side::Black,
+            &[
+                ("c2c1", "+3+1"),
+                ("c3b3", "-3=2"),
+                ("e3d3", "-5=4"),
+                ("g0f0", "17=6"),
+                ("g1f1", "27=6"),
+                ("g2f2", "37=6"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_decode_variants() {
+        let broad_map = board(START_BOARD);
+        for text in ["C2.5", "c2=5", " C 2 = 5 "] {
+            assert_eq!(iccs(decode(&broad_map, Side::White, text).unwrap()), "h2e2");
+        }
+        assert_eq!(iccs(decode(&broad_map, Side::White, "N2+3").unwrap()), "h0g2");
+        assert_eq!(iccs(decode(&broad_map, Side::White, "B3+5").unwrap()), "g0e2");
+        assert_eq!(iccs(decode(&broad_map, Side::Black, "N8+7").unwrap()), "h9g7");
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let broad_map = board(START_BOARD);
+        let format = |text: &str| Err(NotationError::Format(text.to_string()));
+        for text in ["", "C2=", "C2=55", "C2*5", "X2=5", "C0=5", "C2=0", "0P+1", "6P+1"] {
+            assert_eq!(decode(&broad_map, Side::White, text), format(text), "{text}");
+        }
+        let not_found = |text: &str| Err(NotationError::NotFound(text.to_string()));
+        for text in ["R5+1", "+R+1", "-P+1", "H1+2"] {
+            assert_eq!(decode(&broad_map, Side::White, text), not_found(text), "{text}");
+        }
+        let invalid = |text: &str| Err(NotationError::Invalid(text.to_string()));
+        for text in ["H2=3", "H2+5", "R1-1", "E3+9", "A4+4", "K5=4"] {
+            assert_eq!(decode(&broad_map, Side::White, text), invalid(text), "{text}");
+        }
+
+        // 两条纵线都有多个兵时只写 +P 不能确定
+        let broad_map = board("3k5/9/2P3P2/2P3P2/9/9/9/9/9/4K4");
+        let text = "+P+1";
+        let ambiguous = Err(NotationError::Ambiguous(text.to_string()));
+        assert_eq!(decode(&broad_map, Side::White, text), ambiguous);
+        assert_eq!(encode(&broad_map, Pos::new(0, 0), Pos::new(1, 0)), Err(NotationError::NoPiece));
+    }
+
+    /// 穷举各局面中每个棋子所有形状正确的走法, 编码后解码得到原走法且编码互不相同
+    #[test]
+    fn test_exhaustive_round_trip() {
+        let placements = [
+            START_BOARD,
+            "r1bakab1r/9/1cn1c1n2/p1p1p1p1p/9/2P6/P3P1P1P/1CN1C1N2/9/R1BAKAB1R",
+            "3k5/4P4/4P4/4P4/4P4/9/9/9/9/5K3",
+            "3k5/9/2P3P2/2P3P2/9/9/9/9/9/4K4",
+            "4k4/9/9/9/9/9/2p1p4/2p1p1p2/6p2/3K2p2",
+            "3k5/9/4c4/9/4c4/1R7/9/1R7/9/4K4",
+            "3ak1b2/9/2ba5/9/9/9/9/2BA5/9/3AK1B2",
+            "2R6/3k5/R3P4/2NP5/3P5/9/4p4/3n5/3pK4/4r4",
+        ];
+        for placement in placements {
+            let broad_map = board(placement);
+            for side in [Side::White, Side::Black] {
+                let mut seen = Vec::new();
+                for piece in broad_map.iter().flatten().flatten().filter(|p| p.side == side) {
+                    let src = Pos::new(piece.row, piece.col);
+                    for row in 0..10 {
+                        for col in 0..9 {
+                            let dst = Pos::new(row, col);
+                            let (action, n) = describe(piece.kind, side, src, dst);
+                            if destination(&broad_map, side, piece.kind, src, action, n)
+                                != Some(dst)
+                            {
+                                continue;
+                            }
+                            let text = encode(&broad_map, src, dst).unwrap();
+                            let decoded = decode(&broad_map, side, &text);
+                            assert_eq!(decoded, Ok((src, dst)), "{placement} {text}");
+                            assert!(!seen.contains(&text), "{placement} {text}");
+                            seen.push(text);
+                        }
+                    }
+                }
+                assert!(!seen.is_empty());
+            }
+        }
+    }
+}
@@ -0,0 +1,486 @@
+//! 象棋PGN棋谱的导出与导入
+//!
+//! 标签与国际象棋PGN相同, 另有 `[Game "Chinese Chess"]`、`[Format]` 与 `[FEN]`;
+//! 走法可以是ICCS(如 H2-E2)、中文纵线记法或WXF记法, 导入时逐步由引擎检查是否合法
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chessai::position::pos2iccs;
+use chessai::pregen::Winner;
+
+use crate::component::piece::Side;
+use crate::fen;
+use crate::game::Data;
+use crate::notation::{chinese, wxf, BroadMap, Notation};
+use crate::public::{self, Pos, ROUTE_OFFSET};
+
+pub const PGN_GAME: &str = "Chinese Chess";
+pub const PGN_EXTENSION: &str = "pgn";
+
+/// 导入错误, 行号从1起, 读取文件失败时为0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl PgnError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            0 => write!(f, "{}", self.message),
+            line => write!(f, "第{line}行: {}", self.message),
+        }
+    }
+}
+
+/// 标签, 如 [Red "红方"]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub name: String,
+    pub value: String,
+    pub line: usize,
+}
+
+/// 解析后的棋谱: 标签与主线走法, 注释与变着已跳过
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pgn {
+    pub tags: Vec<Tag>,
+    // (行号, 走法)
+    pub moves: Vec<(usize, String)>,
+}
+
+impl Pgn {
+    pub fn tag(&self, name: &str) -> Option<&Tag> {
+        self.tags.iter().find(|tag| tag.name.eq_ignore_ascii_case(name))
+    }
+}
+
+fn winner_text(winner: &Winner) -> &'static str {
+    match winner {
+        Winner::White => "1-0",
+        Winner::Black => "0-1",
+        Winner::Tie => "1/2-1/2",
+    }
+}
+
+/// 优先使用记录的对局结果, 没有时由引擎按局面判定
+fn result_text(data: &mut Data) -> &'static str {
+    if let Some(winner) = &data.result {
+        return winner_text(winner);
+    }
+    data.engine.winner().as_ref().map_or("*", winner_text)
+}
+
+fn format_name(notation: Notation) -> &'static str {
+    match notation {
+        Notation::Chinese => "Chinese",
+        Notation::Wxf => "WXF",
+        Notation::Iccs => "ICCS",
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// 导出棋谱, 走法由开局局面与行棋记录生成
+pub fn export(data: &mut Data, notation: Notation, date: &str) -> String {
+    let result = result_text(data);
+    let tags = [
+        ("Game", PGN_GAME.to_string()),
+        ("Event", public::WIN_TITLE.to_string()),
+        ("Date", date.to_string()),
+        ("Red", data.white_player.title()),
+        ("Black", data.black_player.title()),
+        ("Result", result.to_string()),
+        ("Format", format_name(notation).to_string()),
+        ("FEN", data.start_fen.clone()),
+    ];
+    let mut text = String::new();
+    for (name, value) in tags {
+        text.push_str(&format!("[{name} \"{}\"]\n", escape(&value)));
+    }
+    text.push('\n');
+
+    let start = fen::parse(&data.start_fen).ok();
+    let side = start.map_or(Side::White, |position| position.side);
+    let round = start.map_or(1, |position| position.fullmove());
+    for (i, step) in data.history.iter().enumerate() {
+        let value = match notation {
+            Notation::Iccs => {
+                let iccs = pos2iccs(step.src.row, step.src.col, step.dst.row, step.dst.col);
+                format!("{}-{}", &iccs[..2], &iccs[2..]).to_uppercase()
+            }
+            _ => notation.encode(&data.position_at(i), step.src, step.dst).unwrap_or_else(|_| {
+                pos2iccs(step.src.row, step.src.col, step.dst.row, step.dst.col)
+            }),
+        };
+        // 黑方先行时第一回合只有黑方走法
+        let black = (i + (side == Side::Black) as usize) % 2 == 1;
+        let number = round + (i + (side == Side::Black) as usize) / 2;
+        match (black, i) {
+            (false, _) => text.push_str(&format!("{number}. {value}")),
+            (true, 0) => text.push_str(&format!("{number}. ... {value}\n")),
+            (true, _) => text.push_str(&format!(" {value}\n")),
+        }
+    }
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(result);
+    text.push('\n');
+    text
+}
+
+/// 保存当前对局到棋谱目录, 文件名为保存时间
+pub fn save(data: &mut Data) -> io::Result<PathBuf> {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (year, month, day) = civil_date(secs);
+    let time = secs % 86400;
+    fs::create_dir_all(public::path::GAMES_DIR)?;
+    let path = Path::new(public::path::GAMES_DIR).join(format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}.{PGN_EXTENSION}",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    ));
+    let notation = match data.notation {
+        // 中文记法之外的棋谱多用ICCS交换
+        Notation::Chinese => Notation::Chinese,
+        _ => Notation::Iccs,
+    };
+    fs::write(&path, export(data, notation, &format!("{year:04}.{month:02}.{day:02}")))?;
+    Ok(path)
+}
+
+/// 由UNIX时间计算UTC日期
+fn civil_date(secs: u64) -> (i64, u64, u64) {
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097) as u64;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe as i64 + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+/// 解析棋谱文本: 标签、主线走法, 跳过注释、变着、回合数与结果
+pub fn parse(text: &str) -> Result<Pgn, PgnError> {
+    let mut pgn = Pgn::default();
+    // 未结束的注释与变着的起始行
+    let mut comment: Option<usize> = None;
+    let mut variations: Vec<usize> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let number = i + 1;
+        let trimmed = line.trim();
+        if comment.is_none() && variations.is_empty() && trimmed.starts_with('[') {
+            if !pgn.moves.is_empty() {
+                return Err(PgnError::new(number, "标签应在走法之前"));
+            }
+            pgn.tags.push(parse_tag(trimmed, number)?);
+            continue;
+        }
+        let mut token = String::new();
+        for c in line.chars() {
+            if comment.is_some() {
+                if c == '}' {
+                    comment = None;
+                }
+                continue;
+            }
+            match c {
+                '{' => comment = Some(number),
+                ';' => break,
+                '(' => variations.push(number),
+                ')' => {
+                    if variations.pop().is_none() {
+                        return Err(PgnError::new(number, "变着括号不匹配"));
+                    }
+                }
+                _ if !variations.is_empty() => {}
+                _ if c.is_whitespace() => {}
+                _ => {
+                    token.push(c);
+                    continue;
+                }
+            }
+            push_token(&mut pgn, &mut token, number);
+        }
+        push_token(&mut pgn, &mut token, number);
+    }
+    if let Some(line) = comment {
+        return Err(PgnError::new(line, "注释没有结束"));
+    }
+    if let Some(line) = variations.first() {
+        return Err(PgnError::new(*line, "变着括号不匹配"));
+    }
+    Ok(pgn)
+}
+
+fn parse_tag(line: &str, number: usize) -> Result<Tag, PgnError> {
+    let error = || PgnError::new(number, format!("标签格式错误: {line}"));
+    let inner = line
+        .strip_prefix('[')
+        .and_then(|line| line.strip_suffix(']'))
+        .ok_or_else(error)?;
+    let (name, value) = inner.trim().split_once(char::is_whitespace).ok_or_else(error)?;
+    let value = value.trim();
+    let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).ok_or_else(error)?;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(error());
+    }
+    Ok(Tag {
+        name: name.to_string(),
+        value: value.replace("\\\"", "\"").replace("\\\\", "\\"),
+        line: number,
+    })
+}
+
+/// 去掉回合数、评注符号与结果, 剩下的是走法
+fn push_token(pgn: &mut Pgn, token: &mut String, number: usize) {
+    let text = std::mem::take(token);
+    let mut text = text.as_str();
+    // 回合数可以与走法相连, 如 1.h2e2; WXF的平可以写作 . 如 27.6, 不能当作回合数
+    if let Some(dot) = text.find('.') {
+        let (head, rest) = text.split_at(dot);
+        let rest = rest.trim_start_matches('.');
+        if !head.is_empty()
+            && head.chars().all(|c| c.is_ascii_digit())
+            && !rest.starts_with(|c: char| c.is_ascii_digit())
+        {
+            text = rest;
+        }
+    }
+    let text = text.trim_end_matches(['!', '?']);
+    if text.is_empty()
+        || text.starts_with('$')
+        || text.chars().all(|c| c == '.')
+        || ["1-0", "0-1", "1/2-1/2", "*"].contains(&text)
+    {
+        return;
+    }
+    pgn.moves.push((number, text.to_string()));
+}
+
+/// 解析一步走法: ICCS、中文或WXF记法
+fn parse_move(broad_map: &BroadMap, side: Side, text: &str) -> Option<(Pos, Pos)> {
+    let iccs: String = text.chars().filter(|c| *c != '-').collect::<String>().to_lowercase();
+    let b = iccs.as_bytes();
+    if b.len() == 4
+        && b.iter().step_by(2).all(|c| (b'a'..=b'i').contains(c))
+        && b.iter().skip(1).step_by(2).all(|c| c.is_ascii_digit())
+    {
+        let pos = |col: u8, row: u8| {
+            Pos::new((row - ROUTE_OFFSET.1) as usize, (col - ROUTE_OFFSET.0) as usize)
+        };
+        return Some((pos(b[0], b[1]), pos(b[2], b[3])));
+    }
+    match text.is_ascii() {
+        true => wxf::decode(broad_map, side, text).ok(),
+        false => chinese::decode(broad_map, side, text).ok(),
+    }
+}
+
+/// 由棋谱重建对局: 从开局局面起逐步检查走法并行棋
+pub fn load(pgn: &Pgn) -> Result<Data, PgnError> {
+    let mut data = Data::new();
+    if let Some(tag) = pgn.tag("Game") {
+        if !tag.value.eq_ignore_ascii_case(PGN_GAME) {
+            return Err(PgnError::new(tag.line, format!("不是象棋棋谱: {}", tag.value)));
+        }
+    }
+    if let Some(tag) = pgn.tag("FEN") {
+        data.load_fen(&tag.value)
+            .map_err(|err| PgnError::new(tag.line, format!("FEN格式错误: {err}")))?;
+    } else {
+        data.load_fen(public::START_POS).unwrap();
+    }
+
+    for (line, text) in pgn.moves.iter() {
+        let side = data.current_side.unwrap();
+        let (src, dst) = parse_move(&data.broad_map, side, text)
+            .ok_or_else(|| PgnError::new(*line, format!("无法识别的走法: {text}")))?;
+        if !data.try_move(src, dst) {
+            let message = format!("{}方走法不合法: {text}", side.name());
+            return Err(PgnError::new(*line, message));
+        }
+    }
+    Ok(data)
+}
+
+/// 读取并导入棋谱文件
+pub fn open(path: &Path) -> Result<Data, PgnError> {
+    let text = fs::read_to_string(path).map_err(|err| PgnError::new(0, err.to_string()))?;
+    load(&parse(&text)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::piece::Kind;
+
+    #[test]
+    fn test_parse() {
+        let text = "[Game \"Chinese Chess\"]\n\
+                    [Red \"许银川\"]\n\
+                    [Event \"\\\"甲级\\\"联赛\"]\n\
+                    \n\
+                    1. H2-E2 {中炮} H9-G7 2.h0g2 (2. H0-G2 {变着 (嵌套)} I9-H9) i9h9\n\
+                    3. 车一平二 ; 行末注释 车九进一\n\
+                    3... 马8进7!? $1 4. C2=5 27.6 1-0\n";
+        let pgn = parse(text).unwrap();
+        assert_eq!(pgn.tag("game").unwrap().value, PGN_GAME);
+        assert_eq!(pgn.tag("Red").unwrap().value, "许银川");
+        assert_eq!(pgn.tag("Event").unwrap().value, "\"甲级\"联赛");
+        assert_eq!(pgn.tag("Red").unwrap().line, 2);
+        let moves: Vec<(usize, &str)> = pgn.moves.iter().map(|(n, m)| (*n, m.as_str())).collect();
+        assert_eq!(
+            moves,
+            [
+                (5, "H2-E2"),
+                (5, "H9-G7"),
+                (5, "h0g2"),
+                (5, "i9h9"),
+                (6, "车一平二"),
+                (7, "马8进7"),
+                (7, "C2=5"),
+                (7, "27.6"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |text: &str| parse(text).unwrap_err();
+        assert_eq!(error("[Game Chinese Chess]").line, 1);
+        assert_eq!(error("[Game \"Chinese Chess\"\n").line, 1);
+        assert_eq!(error("1. h2e2\n[Red \"a\"]").line, 2);
+        assert_eq!(error("1. h2e2\nh9g7 {没有结束\n2. h0g2"), PgnError::new(2, "注释没有结束"));
+        assert_eq!(error("1. h2e2\n\n(h9g7").line, 3);
+        assert_eq!(error("1. h2e2 h9g7)").line, 1);
+    }
+
+    fn codes(broad_map: &BroadMap) -> Vec<String> {
+        broad_map
+            .iter()
+            .flatten()
+            .map(|piece| piece.map_or(String::from("."), |piece| piece.code()))
+            .collect()
+    }
+
+    #[test]
+    fn test_load_fen() {
+        let fen = "4k4/9/9/9/9/9/9/9/4A4/3AK4 b - - 0 12";
+        let text = format!("[FEN \"{fen}\"]\n\n12. ... e9d9\n13. E1D2 *\n");
+        let mut data = load(&parse(&text).unwrap()).unwrap();
+        assert_eq!(data.start_fen, fen);
+        assert_eq!(data.broad_map[9][3].unwrap().kind, Kind::King);
+        assert_eq!(data.broad_map[2][3].unwrap().kind, Kind::Advisor);
+        assert_eq!((data.current_side, data.round), (Some(Side::Black), 13));
+        assert_eq!(data.to_fen(), "3k5/9/9/9/9/9/9/3A5/9/3AK4 b - - 2 13");
+        let exported = export(&mut data, Notation::Iccs, "2026.10.18");
+        // 双方都没有进攻子力, 引擎判和
+        assert!(exported.contains("[Result \"1/2-1/2\"]"), "{exported}");
+        assert!(exported.contains("12. ... E9-D9\n13. E1-D2\n1/2-1/2\n"), "{exported}");
+    }
+
+    #[test]
+    fn test_load() {
+        let text = "[Game \"Chinese Chess\"]\n\n1. H2-E2 马8进7\n2. H0G2 R9=8 *\n";
+        let data = load(&parse(text).unwrap()).unwrap();
+        assert_eq!(data.history.len(), 4);
+        assert_eq!(data.current_side, Some(Side::White));
+        assert_eq!(data.round, 2);
+        assert_eq!(data.broad_map[2][4].unwrap().kind, Kind::Cannon);
+        assert_eq!(data.broad_map[9][7].unwrap().kind, Kind::Rook);
+        assert!(data.broad_map[9][8].is_none());
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let error = |text: &str| load(&parse(text).unwrap()).err().unwrap();
+        assert_eq!(error("[Game \"Chess\"]").line, 1);
+        assert_eq!(error("[FEN \"rnbakabnr/9\"]\n1. h2e2").line, 1);
+        assert_eq!(
+            error("1. h2e2 h9g7\n2. 炮五进三"),
+            PgnError::new(2, "红方走法不合法: 炮五进三")
+        );
+        assert_eq!(error("1. h2e2\n\nh2h3").line, 3);
+        assert_eq!(error("1. h2e2 车1进3"), PgnError::new(1, "无法识别的走法: 车1进3"));
+        assert_eq!(error("1. h2e2 xyz").message, "无法识别的走法: xyz");
+    }
+
+    #[test]
+    fn test_export_import() {
+        let mut data = Data::new();
+        data.current_side = Some(Side::White);
+        data.engine.from_fen(public::START_POS);
+        let moves = [
+            (Pos::new(2, 7), Pos::new(2, 4)),
+            (Pos::new(9, 7), Pos::new(7, 6)),
+            (Pos::new(2, 4), Pos::new(6, 4)),
+            (Pos::new(9, 1), Pos::new(7, 2)),
+            (Pos::new(0, 7), Pos::new(2, 6)),
+        ];
+        for (src, dst) in moves {
+            data.make_move(src, dst);
+            data.change_side();
+        }
+        for notation in [Notation::Iccs, Notation::Chinese, Notation::Wxf] {
+            let text = export(&mut data, notation, "2026.10.18");
+            assert!(text.starts_with("[Game \"Chinese Chess\"]\n"));
+            assert!(text.contains("[Date \"2026.10.18\"]"));
+            assert!(text.contains(&format!("[FEN \"{}\"]", public::START_POS)));
+            let loaded = load(&parse(&text).unwrap()).unwrap();
+            let history: Vec<(Pos, Pos)> = loaded.history.iter().map(|s| (s.src, s.dst)).collect();
+            assert_eq!(history, moves, "{text}");
+            assert_eq!(codes(&loaded.broad_map), codes(&data.broad_map));
+        }
+        let text = export(&mut data, Notation::Chinese, "2026.10.18");
+        assert!(text.contains("1. 炮二平五 马8进7\n2. 炮五进四 马2进3\n3. 马二进三\n*\n"));
+    }
+
+    #[test]
+    fn test_export_result() {
+        let mut data = Data::new();
+        data.load_fen(public::START_POS).unwrap();
+        data.try_move(Pos::new(2, 7), Pos::new(2, 4));
+        // 黑方认输, 局面无法判定胜负, 导出记录的结果
+        data.result = Some(Winner::White);
+        let text = export(&mut data, Notation::Iccs, "2026.10.18");
+        assert!(text.contains("[Result \"1-0\"]"), "{text}");
+        assert!(text.contains("1. H2-E2\n1-0\n"), "{text}");
+
+        // 新局清除对局结果
+        data.reset();
+        assert!(data.result.is_none());
+        let text = export(&mut data, Notation::Iccs, "2026.10.18");
+        assert!(text.contains("[Result \"*\"]"), "{text}");
+        data.result = Some(Winner::Tie);
+        data.load_fen(public::START_POS).unwrap();
+        assert!(data.result.is_none());
+    }
+
+    #[test]
+    fn test_civil_date() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(951782400), (2000, 2, 29));
+        assert_eq!(civil_date(1792281600), (2026, 10, 18));
+    }
+}
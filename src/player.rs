@@ -1,7 +1,89 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
 
 use crate::component::piece::Side;
 
+// 最后几秒读秒报警
+pub const ALARM_TIME: Duration = Duration::from_secs(10);
+
+/// 时限规则: 基本局时 + 每步加秒 + 读秒
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeControl {
+    // 名称
+    pub name: &'static str,
+    // 基本局时
+    pub base: Duration,
+    // 每走一步加秒(费舍尔制)
+    pub increment: Duration,
+    // 读秒次数
+    pub byoyomi_periods: u32,
+    // 每次读秒时长
+    pub byoyomi_time: Duration,
+}
+
+// 可选的时限规则, 主菜单中依次切换
+pub const TIME_CONTROLS: [TimeControl; 4] = [
+    // 局时用完即负
+    TimeControl {
+        name: "快棋10分",
+        base: Duration::from_secs(10 * 60),
+        increment: Duration::ZERO,
+        byoyomi_periods: 0,
+        byoyomi_time: Duration::ZERO,
+    },
+    TimeControl {
+        name: "加秒5+5",
+        base: Duration::from_secs(5 * 60),
+        increment: Duration::from_secs(5),
+        byoyomi_periods: 0,
+        byoyomi_time: Duration::ZERO,
+    },
+    TimeControl {
+        name: "读秒20分",
+        base: Duration::from_secs(20 * 60),
+        increment: Duration::ZERO,
+        byoyomi_periods: 3,
+        byoyomi_time: Duration::from_secs(30),
+    },
+    TimeControl {
+        name: "慢棋60分",
+        base: Duration::from_secs(60 * 60),
+        increment: Duration::ZERO,
+        byoyomi_periods: 1,
+        byoyomi_time: Duration::from_secs(60),
+    },
+];
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        TIME_CONTROLS[2]
+    }
+}
+
+impl TimeControl {
+    /// 在可选时限规则中的序号
+    pub fn index(&self) -> usize {
+        TIME_CONTROLS.iter().position(|tc| tc == self).unwrap_or(0)
+    }
+
+    /// 下一个可选的时限规则
+    pub fn next(&self) -> Self {
+        TIME_CONTROLS[(self.index() + 1) % TIME_CONTROLS.len()]
+    }
+}
+
+/// 计时结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerState {
+    // 正常计时
+    Normal,
+    // 最后几秒, 每秒报警一次
+    Alarm,
+    // 刚刚超时
+    Timeout,
+}
+
 #[derive(Component, Clone, Debug)]
 pub struct Record {
     pub serial: usize,
@@ -9,10 +91,45 @@ pub struct Record {
     pub value: String,
 }
 
+/// AI难度
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AiLevel {
+    // 入门
+    Beginner,
+    // 业余
+    #[default]
+    Amateur,
+    // 专业
+    Expert,
+    // 大师
+    Master,
+}
+
+impl AiLevel {
+    pub fn name(&self) -> &str {
+        match self {
+            AiLevel::Beginner => "入门",
+            AiLevel::Amateur => "业余",
+            AiLevel::Expert => "专业",
+            AiLevel::Master => "大师",
+        }
+    }
+
+    /// 随机走子的概率
+    pub fn blunder_rate(&self) -> f64 {
+        match self {
+            AiLevel::Beginner => 0.3,
+            AiLevel::Amateur => 0.1,
+            AiLevel::Expert => 0.03,
+            AiLevel::Master => 0.0,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 pub enum Id {
     // 电脑方
-    Ai,
+    Ai(AiLevel),
     // 主场方
     #[default]
     Home,
@@ -24,23 +141,222 @@ pub enum Id {
 pub struct Player {
     pub id: Id,
     pub side: Side,
+    // 时限规则
+    pub time_control: TimeControl,
+    // 局时剩余
+    pub global_timer: Duration,
+    // 本步已用时
+    pub current_timer: Duration,
+    // 本次读秒剩余
+    pub byoyomi_timer: Duration,
+    // 读秒剩余次数
+    pub byoyomi_periods: u32,
 }
 
 impl Player {
     pub fn new_white() -> Self {
-        Self {
-            side: Side::White,
-            id: Id::default(),
-        }
+        Self::new(Side::White)
     }
 
     pub fn new_black() -> Self {
-        Self {
-            side: Side::Black,
+        Self::new(Side::Black)
+    }
+
+    fn new(side: Side) -> Self {
+        let mut player = Self {
+            side,
             id: Id::default(),
+            time_control: TimeControl::default(),
+            global_timer: Duration::ZERO,
+            current_timer: Duration::ZERO,
+            byoyomi_timer: Duration::ZERO,
+            byoyomi_periods: 0,
+        };
+        player.set_time_control(TimeControl::default());
+        player
+    }
+
+    /// 设置时限规则并重置计时
+    pub fn set_time_control(&mut self, time_control: TimeControl) {
+        self.time_control = time_control;
+        self.global_timer = time_control.base;
+        self.current_timer = Duration::ZERO;
+        self.byoyomi_timer = time_control.byoyomi_time;
+        self.byoyomi_periods = match time_control.byoyomi_time.is_zero() {
+            true => 0,
+            false => time_control.byoyomi_periods,
+        };
+    }
+
+    pub fn get_global_timer(&self) -> String {
+        format!("局时: {}", format_timer(self.global_timer))
+    }
+
+    pub fn get_current_timer(&self) -> String {
+        if self.in_byoyomi() {
+            format!("读秒: {}({})", format_timer(self.byoyomi_timer), self.byoyomi_periods)
+        } else {
+            format!("步时: {}", format_timer(self.current_timer))
+        }
+    }
+
+    /// 计时, 进入最后几秒时每秒报警一次
+    pub fn tick(&mut self, delta: Duration) -> TimerState {
+        if self.is_timeout() {
+            return TimerState::Normal;
+        }
+        let before = self.countdown();
+        self.current_timer += delta;
+
+        // 先消耗局时, 局时用完后进入读秒
+        let used = delta.min(self.global_timer);
+        self.global_timer -= used;
+        let mut delta = delta - used;
+        while !delta.is_zero() && self.byoyomi_periods > 0 {
+            let used = delta.min(self.byoyomi_timer);
+            self.byoyomi_timer -= used;
+            delta -= used;
+            if self.byoyomi_timer.is_zero() {
+                // 本次读秒用完, 扣除一次读秒
+                self.byoyomi_periods -= 1;
+                self.byoyomi_timer = self.time_control.byoyomi_time;
+            }
+        }
+
+        if self.is_timeout() {
+            return TimerState::Timeout;
+        }
+        match (before, self.countdown()) {
+            (Some(before), Some(after))
+                if after <= ALARM_TIME && ceil_secs(after) < ceil_secs(before) =>
+            {
+                TimerState::Alarm
+            }
+            _ => TimerState::Normal,
+        }
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        self.global_timer.is_zero() && self.byoyomi_periods == 0
+    }
+
+    /// 局时已用完, 正在读秒
+    pub fn in_byoyomi(&self) -> bool {
+        self.global_timer.is_zero() && self.byoyomi_periods > 0
+    }
+
+    /// 超时或扣除读秒前的倒计时, 局时之后还有读秒时为None
+    fn countdown(&self) -> Option<Duration> {
+        if self.in_byoyomi() {
+            Some(self.byoyomi_timer)
+        } else if self.byoyomi_periods == 0 {
+            Some(self.global_timer)
+        } else {
+            None
+        }
+    }
+
+    /// 本方走完一步: 加秒并重置步时
+    pub fn end_move(&mut self) {
+        if !self.global_timer.is_zero() {
+            self.global_timer += self.time_control.increment;
+        }
+        self.reset_current_timer();
+    }
+
+    /// 重置步时, 读秒中未用完的本次读秒恢复满额
+    pub fn reset_current_timer(&mut self) {
+        self.current_timer = Duration::ZERO;
+        self.byoyomi_timer = self.time_control.byoyomi_time;
+    }
+
+    /// 信息框标题
+    pub fn title(&self) -> String {
+        match self.id {
+            Id::Ai(level) => format!("{}方·电脑·{}", self.side.name(), level.name()),
+            Id::Away => format!("{}方·对手", self.side.name()),
+            _ => format!("{}方", self.side.name()),
         }
     }
 }
 
 #[derive(Component)]
 pub struct PlayerFocus;
+
+/// 倒计时显示为 mm:ss, 不足一秒按一秒显示
+pub fn format_timer(timer: Duration) -> String {
+    let secs = ceil_secs(timer);
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+fn ceil_secs(timer: Duration) -> u128 {
+    timer.as_millis().div_ceil(1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(time_control: TimeControl) -> Player {
+        let mut player = Player::new_white();
+        player.set_time_control(time_control);
+        player
+    }
+
+    #[test]
+    fn test_sudden_death() {
+        let mut player = player(TIME_CONTROLS[0]);
+        assert_eq!(player.tick(Duration::from_secs(9 * 60)), TimerState::Normal);
+        assert_eq!(player.tick(Duration::from_secs(49)), TimerState::Normal);
+        // 最后10秒开始报警
+        assert_eq!(player.tick(Duration::from_millis(1500)), TimerState::Alarm);
+        assert_eq!(player.tick(Duration::from_millis(100)), TimerState::Normal);
+        assert_eq!(player.tick(Duration::from_secs(60)), TimerState::Timeout);
+        assert!(player.is_timeout());
+        // 超时后不再重复判负
+        assert_eq!(player.tick(Duration::from_secs(1)), TimerState::Normal);
+    }
+
+    #[test]
+    fn test_increment() {
+        let mut player = player(TIME_CONTROLS[1]);
+        player.tick(Duration::from_secs(3));
+        player.end_move();
+        assert_eq!(player.global_timer, Duration::from_secs(5 * 60 + 2));
+        assert_eq!(player.current_timer, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_byoyomi() {
+        let mut player = player(TIME_CONTROLS[2]);
+        // 局时用完进入读秒, 不报警
+        assert_eq!(player.tick(Duration::from_secs(20 * 60)), TimerState::Normal);
+        assert!(player.in_byoyomi());
+        assert_eq!(player.get_current_timer(), "读秒: 00:30(3)");
+
+        // 读秒内走完一步, 读秒次数不变
+        player.tick(Duration::from_secs(25));
+        player.end_move();
+        assert_eq!(player.byoyomi_timer, Duration::from_secs(30));
+        assert_eq!(player.byoyomi_periods, 3);
+
+        // 用完一次读秒, 扣除一次
+        player.tick(Duration::from_secs(31));
+        assert_eq!(player.byoyomi_periods, 2);
+        assert_eq!(player.byoyomi_timer, Duration::from_secs(29));
+        assert_eq!(player.tick(Duration::from_secs(20)), TimerState::Alarm);
+
+        // 读秒全部用完判负
+        assert_eq!(player.tick(Duration::from_secs(39)), TimerState::Timeout);
+        assert_eq!(player.byoyomi_periods, 0);
+    }
+
+    #[test]
+    fn test_next_time_control() {
+        let mut time_control = TimeControl::default();
+        for _ in 0..TIME_CONTROLS.len() {
+            time_control = time_control.next();
+        }
+        assert_eq!(time_control, TimeControl::default());
+    }
+}
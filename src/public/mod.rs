@@ -21,13 +21,40 @@ pub const WIN_SIZE: Size = Size {
 };
 pub const ROUTE_OFFSET: (u8, u8) = (97, 48);
 
-pub fn get_piece_render_percent(row: usize, col: usize) -> (f32, f32) {
+pub fn get_piece_render_percent(row: usize, col: usize, flipped: bool) -> (f32, f32) {
+    // 翻转棋盘时黑方在下
+    let (row, col) = if flipped {
+        (9 - row, 8 - col)
+    } else {
+        (row, col)
+    };
     (
         -274_f32 + (col as f32 * 68_f32), // 300
         -285_f32 + (row as f32 * 68_f32), // 311
     )
 }
 
+/// 根据鼠标位置计算棋盘坐标, 不在棋子范围内时返回None
+pub fn get_piece_pos(x: f32, y: f32, flipped: bool) -> Option<(usize, usize)> {
+    let col = ((x + 274_f32) / 68_f32).round();
+    let row = ((y + 285_f32) / 68_f32).round();
+    if !(0_f32..=8_f32).contains(&col) || !(0_f32..=9_f32).contains(&row) {
+        return None;
+    }
+    let (row, col) = (row as usize, col as usize);
+    let (piece_x, piece_y) = get_piece_render_percent(row, col, false);
+
+    // 计算选择点是否超出棋子边缘: 选择点到棋心的直线距离是否大于30
+    if ((piece_x - x).powi(2) + (piece_y - y).powi(2)).sqrt() > 30_f32 {
+        return None;
+    }
+    if flipped {
+        Some((9 - row, 8 - col))
+    } else {
+        Some((row, col))
+    }
+}
+
 #[derive(Resource)]
 pub struct EntityResources {
     pub pending_menus: Option<Entity>,
@@ -46,7 +73,7 @@ pub struct BroadEntitys {
     pub pieces: [[Option<Entity>; 9]; 10],
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Pos {
     pub row: usize,
     pub col: usize,
@@ -57,3 +84,25 @@ impl Pos {
         Self { row, col }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_piece_pos() {
+        for flipped in [false, true] {
+            for row in 0..10 {
+                for col in 0..9 {
+                    let (x, y) = get_piece_render_percent(row, col, flipped);
+                    assert_eq!(get_piece_pos(x + 10_f32, y - 10_f32, flipped), Some((row, col)));
+                }
+            }
+        }
+        assert_eq!(get_piece_render_percent(0, 0, true), get_piece_render_percent(9, 8, false));
+        // 棋子边缘之外
+        assert_eq!(get_piece_pos(-274_f32 + 34_f32, -285_f32, false), None);
+        // 棋盘之外
+        assert_eq!(get_piece_pos(-274_f32 + 9_f32 * 68_f32, -285_f32, false), None);
+    }
+}
@@ -1,3 +1,6 @@
+// 保存的棋谱
+pub const GAMES_DIR: &str = "games";
+
 // 字体: 文楷
 pub const FONT_WENKAI: &str = "font/wenkai.ttf";
 // 字体: 汉中小隶
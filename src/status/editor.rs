@@ -0,0 +1,628 @@
+use std::fmt;
+
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use super::{ChessState, GameState};
+use crate::{
+    component::{
+        piece::{Kind, Piece, Side},
+        ChineseBroadCamera,
+    },
+    fen::{self, FenError},
+    game::{Data, GameMode},
+    notation::BroadMap,
+    public::{self, get_piece_pos, get_piece_render_percent},
+};
+
+// 摆棋配置
+pub const EDITOR_NORMAL_BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+pub const EDITOR_HOVERED_BUTTON_COLOR: Color = Color::rgb(0.30, 0.30, 0.30);
+pub const EDITOR_PRESSED_BUTTON_COLOR: Color = Color::rgb(0.45, 0.45, 0.45);
+pub const EDITOR_CLEAR_TEXT: &str = "清空";
+pub const EDITOR_INITIAL_TEXT: &str = "初始局面";
+pub const EDITOR_AI_GAME_TEXT: &str = "人机对弈";
+pub const EDITOR_DEDUCE_GAME_TEXT: &str = "打谱推演";
+pub const EDITOR_BACK_TEXT: &str = "返回";
+pub const EDITOR_HINT: &str = "从两侧拖动棋子摆放, 拖出棋盘或右键删除";
+
+// 棋子栏的兵种顺序
+const PALETTE_KINDS: [Kind; 7] = [
+    Kind::King,
+    Kind::Advisor,
+    Kind::Bishop,
+    Kind::Knight,
+    Kind::Rook,
+    Kind::Cannon,
+    Kind::Pawn,
+];
+
+/// 局面不合法的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SetupError {
+    // 子力数量错误
+    Count(FenError),
+    // 棋子在走不到的位置
+    Square(Side, Kind, usize, usize),
+    // 将帅照面
+    KingsFacing,
+    // 不行棋的一方正被将军
+    Checked(Side),
+}
+
+impl fmt::Display for SetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetupError::Count(err) => write!(f, "{err}"),
+            SetupError::Square(side, kind, row, col) => {
+                let file = (b'a' + *col as u8) as char;
+                write!(f, "{}{}不能在{file}{row}", side.name(), kind.name())
+            }
+            SetupError::KingsFacing => write!(f, "将帅不能照面"),
+            SetupError::Checked(side) => {
+                write!(f, "{0}方正被将军, 应由{0}方行棋", side.name())
+            }
+        }
+    }
+}
+
+/// 棋子能否到达所在位置: 帅士在九宫内的规定点, 相在己方规定点, 兵未过河时只在兵线上
+fn reachable(piece: &Piece) -> bool {
+    // 换算到红方视角, 规定点左右对称
+    let (row, col) = match piece.side {
+        Side::White => (piece.row, piece.col),
+        Side::Black => (9 - piece.row, piece.col),
+    };
+    match piece.kind {
+        Kind::King => row <= 2 && (3..=5).contains(&col),
+        Kind::Advisor => matches!((row, col), (0, 3) | (0, 5) | (1, 4) | (2, 3) | (2, 5)),
+        Kind::Bishop => {
+            matches!((row, col), (0, 2) | (0, 6) | (2, 0) | (2, 4) | (2, 8) | (4, 2) | (4, 6))
+        }
+        Kind::Pawn => row >= 5 || (row >= 3 && col % 2 == 0),
+        _ => true,
+    }
+}
+
+/// 双方帅(将)的位置
+fn kings(broad_map: &BroadMap) -> Vec<Piece> {
+    broad_map
+        .iter()
+        .flatten()
+        .flatten()
+        .filter(|piece| piece.kind == Kind::King)
+        .copied()
+        .collect()
+}
+
+/// 检查摆好的局面: 子力数量、棋子位置、将帅照面, 以及不行棋的一方没有被将军
+pub fn check(broad_map: &BroadMap, side: Side) -> Result<(), SetupError> {
+    fen::validate(broad_map).map_err(SetupError::Count)?;
+    for piece in broad_map.iter().flatten().flatten() {
+        if !reachable(piece) {
+            return Err(SetupError::Square(piece.side, piece.kind, piece.row, piece.col));
+        }
+    }
+    if let [a, b] = kings(broad_map)[..] {
+        let (low, high) = (a.row.min(b.row), a.row.max(b.row));
+        if a.col == b.col && (low + 1..high).all(|row| broad_map[row][a.col].is_none()) {
+            return Err(SetupError::KingsFacing);
+        }
+    }
+    // 由引擎判断轮到对方行棋时是否将军
+    let mut engine = chessai::Engine::new();
+    engine.from_fen(&fen::serialize(&fen::Position::new(*broad_map, side.opponent())));
+    if engine.in_check() {
+        return Err(SetupError::Checked(side.opponent()));
+    }
+    Ok(())
+}
+
+/// 摆棋中的局面, 进入摆棋时创建, 离开时清除
+#[derive(Resource)]
+pub struct Editor {
+    // 摆放中的棋盘地图
+    pub broad_map: BroadMap,
+    // 行棋方
+    pub side: Side,
+    // 正在拖动的棋子
+    dragging: Option<Piece>,
+    // 棋盘
+    broad: Entity,
+}
+
+impl Editor {
+    /// 清空棋盘, 只保留双方帅(将)
+    pub fn clear(&mut self) {
+        for piece in self.broad_map.iter_mut().flatten() {
+            if piece.is_some_and(|piece| piece.kind != Kind::King) {
+                *piece = None;
+            }
+        }
+    }
+
+    /// 放下棋子, 覆盖原有棋子
+    pub fn place(&mut self, mut piece: Piece, row: usize, col: usize) {
+        piece.row = row;
+        piece.col = col;
+        self.broad_map[row][col] = Some(piece);
+    }
+}
+
+/// 摆棋的棋盘
+#[derive(Component)]
+pub struct EditorBroad;
+
+/// 棋盘上的棋子
+#[derive(Component)]
+pub struct EditorPiece;
+
+/// 随鼠标移动的棋子
+#[derive(Component)]
+pub struct EditorDragPiece;
+
+/// 局面检查结果
+#[derive(Component)]
+pub struct EditorStatus;
+
+/// 行棋方按钮文本
+#[derive(Component)]
+pub struct EditorSideText;
+
+/// 摆棋按钮
+#[derive(Component, Clone, Copy)]
+pub enum EditorMenu {
+    // 棋子栏, 按下后拖动到棋盘
+    Palette(Side, Kind),
+    // 切换行棋方
+    Side,
+    // 清空棋盘
+    Clear,
+    // 恢复开局局面
+    Initial,
+    // 人机对弈, 玩家执行棋方
+    AiGame,
+    // 打谱推演
+    DeduceGame,
+    // 返回主菜单
+    Back,
+}
+
+fn side_text(side: Side) -> String {
+    format!("{}方先行", side.name())
+}
+
+/// 摆棋界面: 中间为棋盘, 左右两侧为双方棋子栏与按钮
+pub fn spawn_editor(
+    commands: &mut Commands,
+    fonts: &public::asset::Fonts,
+    image_handles: &public::asset::Images,
+    piece_handles: &public::asset::Pieces,
+) -> Entity {
+    let broad = commands
+        .spawn((
+            SpriteBundle {
+                texture: image_handles.broad.clone(),
+                transform: Transform::IDENTITY,
+                ..default()
+            },
+            EditorBroad,
+        ))
+        .id();
+    let position = fen::parse(public::START_POS).unwrap();
+    commands.insert_resource(Editor {
+        broad_map: position.broad_map,
+        side: position.side,
+        dragging: None,
+        broad,
+    });
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100_f32),
+                height: Val::Percent(100_f32),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    EDITOR_HINT,
+                    TextStyle {
+                        font: fonts.wenkai.clone(),
+                        font_size: 24_f32,
+                        color: Color::ANTIQUE_WHITE,
+                    },
+                )
+                .with_style(Style {
+                    top: Val::Px(8_f32),
+                    position_type: PositionType::Absolute,
+                    ..default()
+                }),
+                EditorStatus,
+            ));
+            // 红方棋子栏在左, 黑方在右
+            let columns = [
+                (
+                    Side::White,
+                    Val::Px(40_f32),
+                    Val::Auto,
+                    [EditorMenu::Side, EditorMenu::Clear, EditorMenu::Initial],
+                ),
+                (
+                    Side::Black,
+                    Val::Auto,
+                    Val::Px(40_f32),
+                    [EditorMenu::AiGame, EditorMenu::DeduceGame, EditorMenu::Back],
+                ),
+            ];
+            for (side, left, right, menus) in columns {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            left,
+                            right,
+                            top: Val::Percent(12_f32),
+                            width: Val::Px(240_f32),
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        // 棋子栏
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    width: Val::Px(240_f32),
+                                    flex_wrap: FlexWrap::Wrap,
+                                    justify_content: JustifyContent::Center,
+                                    margin: UiRect::bottom(Val::Px(16_f32)),
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                for kind in PALETTE_KINDS {
+                                    let piece = Piece {
+                                        side,
+                                        kind,
+                                        row: 0,
+                                        col: 0,
+                                    };
+                                    parent.spawn((
+                                        ButtonBundle {
+                                            style: Style {
+                                                width: Val::Px(72_f32),
+                                                height: Val::Px(72_f32),
+                                                margin: UiRect::all(Val::Px(4_f32)),
+                                                ..default()
+                                            },
+                                            image: UiImage::new(
+                                                piece_handles.get_handle(&piece, false),
+                                            ),
+                                            ..default()
+                                        },
+                                        EditorMenu::Palette(side, kind),
+                                    ));
+                                }
+                            });
+                        for menu in menus {
+                            make_editor_button(parent, fonts.xiaoli.clone(), menu);
+                        }
+                    });
+            }
+        })
+        .id()
+}
+
+fn make_editor_button(parent: &mut ChildBuilder, font: Handle<Font>, menu: EditorMenu) {
+    let text = match menu {
+        EditorMenu::Side => side_text(Side::White),
+        EditorMenu::Clear => EDITOR_CLEAR_TEXT.to_string(),
+        EditorMenu::Initial => EDITOR_INITIAL_TEXT.to_string(),
+        EditorMenu::AiGame => EDITOR_AI_GAME_TEXT.to_string(),
+        EditorMenu::DeduceGame => EDITOR_DEDUCE_GAME_TEXT.to_string(),
+        EditorMenu::Back | EditorMenu::Palette(..) => EDITOR_BACK_TEXT.to_string(),
+    };
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(204_f32),
+                    height: Val::Px(56_f32),
+                    margin: UiRect::all(Val::Px(6_f32)),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: EDITOR_NORMAL_BUTTON_COLOR.into(),
+                ..default()
+            },
+            menu,
+        ))
+        .with_children(|parent| {
+            let text = TextBundle::from_section(
+                text,
+                TextStyle {
+                    font,
+                    font_size: 34_f32,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            );
+            match menu {
+                EditorMenu::Side => parent.spawn((text, EditorSideText)),
+                _ => parent.spawn(text),
+            };
+        });
+}
+
+/// 离开摆棋: 销毁棋盘并清除摆棋局面
+fn close(commands: &mut Commands, editor: &Editor) {
+    commands.entity(editor.broad).despawn_recursive();
+    commands.remove_resource::<Editor>();
+}
+
+pub fn editor_button_system(
+    mut commands: Commands,
+    fonts: Res<public::asset::Fonts>,
+    mut data: ResMut<Data>,
+    mut editor: ResMut<Editor>,
+    mut entitys: ResMut<public::EntityResources>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut chess_state: ResMut<NextState<ChessState>>,
+    mut query: Query<
+        (&Interaction, &mut BackgroundColor, &EditorMenu),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interact, mut color, menu) in &mut query {
+        // 棋子栏按钮显示棋子图片, 不改变颜色
+        if let EditorMenu::Palette(side, kind) = *menu {
+            if *interact == Interaction::Pressed {
+                editor.dragging = Some(Piece {
+                    side,
+                    kind,
+                    row: 0,
+                    col: 0,
+                });
+            }
+            continue;
+        }
+        match *interact {
+            Interaction::Pressed => {
+                *color = EDITOR_PRESSED_BUTTON_COLOR.into();
+                let mode = match menu {
+                    EditorMenu::Side => {
+                        editor.side = editor.side.opponent();
+                        continue;
+                    }
+                    EditorMenu::Clear => {
+                        editor.clear();
+                        continue;
+                    }
+                    EditorMenu::Initial => {
+                        editor.broad_map = fen::parse(public::START_POS).unwrap().broad_map;
+                        continue;
+                    }
+                    EditorMenu::Back => {
+                        close(&mut commands, &editor);
+                        commands.entity(entitys.pending_menus.unwrap()).despawn_recursive();
+                        entitys.pending_menus =
+                            Some(super::pending::spawn_main_menu(&mut commands, &data, &fonts));
+                        continue;
+                    }
+                    EditorMenu::AiGame => GameMode::AiGame,
+                    EditorMenu::DeduceGame => GameMode::DeduceGame,
+                    EditorMenu::Palette(..) => continue,
+                };
+                if let Err(err) = check(&editor.broad_map, editor.side) {
+                    info!("摆棋局面不合法: {err}");
+                    continue;
+                }
+                // 玩家执行棋方, AI执对方
+                data.mode = Some(mode);
+                if mode == GameMode::AiGame {
+                    data.set_ai_side(editor.side.opponent());
+                }
+                data.set_position(&fen::Position::new(editor.broad_map, editor.side));
+                info!("摆棋开局 {}", data.start_fen);
+                close(&mut commands, &editor);
+                game_state.set(GameState::RUNNING);
+                chess_state.set(data.chess_state());
+            }
+            Interaction::Hovered => {
+                *color = EDITOR_HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *color = EDITOR_NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+/// 拖放棋子: 按下拿起棋盘上的棋子, 松开时放到棋盘上, 拖出棋盘则删除; 右键删除棋子
+pub fn drag_system(
+    mut commands: Commands,
+    mut editor: ResMut<Editor>,
+    buttons: Res<Input<MouseButton>>,
+    piece_handles: Res<public::asset::Pieces>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<ChineseBroadCamera>>,
+    mut q_drag: Query<(Entity, &mut Transform), With<EditorDragPiece>>,
+) {
+    let (camera, camera_transform) = q_camera.single();
+    let Some(cursor) = q_window
+        .single()
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+        .map(|ray| ray.origin.truncate())
+    else {
+        return;
+    };
+    let square = get_piece_pos(cursor.x, cursor.y, false);
+
+    if let Some((row, col)) = square {
+        if editor.dragging.is_none() && editor.broad_map[row][col].is_some() {
+            if buttons.just_pressed(MouseButton::Left) {
+                editor.dragging = editor.broad_map[row][col].take();
+            } else if buttons.just_pressed(MouseButton::Right) {
+                info!("删除棋子 {}", editor.broad_map[row][col].unwrap().name());
+                editor.broad_map[row][col] = None;
+            }
+        }
+    }
+
+    let Some(piece) = editor.dragging else {
+        return;
+    };
+    if buttons.just_released(MouseButton::Left) {
+        editor.dragging = None;
+        match square {
+            Some((row, col)) => editor.place(piece, row, col),
+            None => info!("删除棋子 {}", piece.name()),
+        }
+        for (entity, _) in q_drag.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+    match q_drag.get_single_mut() {
+        Ok((_, mut transform)) => {
+            transform.translation = cursor.extend(5_f32);
+        }
+        Err(_) => {
+            commands.spawn((
+                SpriteBundle {
+                    texture: piece_handles.get_handle(&piece, true),
+                    transform: Transform::from_translation(cursor.extend(5_f32)),
+                    sprite: Sprite {
+                        custom_size: Some(Vec2::new(76_f32, 76_f32)),
+                        ..default()
+                    },
+                    ..default()
+                },
+                EditorDragPiece,
+            ));
+        }
+    }
+}
+
+/// 局面变化时重新渲染棋子, 显示检查结果与行棋方
+pub fn render_system(
+    mut commands: Commands,
+    editor: Res<Editor>,
+    piece_handles: Res<public::asset::Pieces>,
+    q_piece: Query<Entity, With<EditorPiece>>,
+    mut status_q: Query<&mut Text, (With<EditorStatus>, Without<EditorSideText>)>,
+    mut side_q: Query<&mut Text, (With<EditorSideText>, Without<EditorStatus>)>,
+) {
+    if !editor.is_changed() {
+        return;
+    }
+    for entity in q_piece.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.entity(editor.broad).with_children(|parent| {
+        for piece in editor.broad_map.iter().flatten().flatten() {
+            let (x, y) = get_piece_render_percent(piece.row, piece.col, false);
+            parent.spawn((
+                SpriteBundle {
+                    texture: piece_handles.get_handle(piece, false),
+                    transform: Transform::from_xyz(x, y, 1_f32),
+                    sprite: Sprite {
+                        custom_size: Some(Vec2::new(76_f32, 76_f32)),
+                        ..default()
+                    },
+                    ..default()
+                },
+                EditorPiece,
+            ));
+        }
+    });
+
+    let (status, color) = match check(&editor.broad_map, editor.side) {
+        Ok(()) => (EDITOR_HINT.to_string(), Color::ANTIQUE_WHITE),
+        Err(err) => (err.to_string(), Color::ORANGE_RED),
+    };
+    for mut text in status_q.iter_mut() {
+        text.sections[0].value = status.clone();
+        text.sections[0].style.color = color;
+    }
+    for mut text in side_q.iter_mut() {
+        text.sections[0].value = side_text(editor.side);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::testing::{board, START_BOARD};
+
+    #[test]
+    fn test_check() {
+        let start = board(START_BOARD);
+        assert_eq!(check(&start, Side::White), Ok(()));
+        assert_eq!(check(&start, Side::Black), Ok(()));
+        assert_eq!(check(&board("3k5/9/9/9/9/9/9/9/4A4/4K4"), Side::White), Ok(()));
+        // 过河兵可以在任意位置, 未过河时只在兵线
+        assert_eq!(check(&board("3k5/9/9/pP7/9/9/9/9/9/4K4"), Side::White), Ok(()));
+    }
+
+    #[test]
+    fn test_check_errors() {
+        let cases = [
+            ("3k5/9/9/9/9/9/9/9/9/9", SetupError::Count(FenError::King(Side::White, 0))),
+            (
+                "3k5/9/9/9/9/9/9/9/9/RRR1K4",
+                SetupError::Count(FenError::TooMany(Side::White, Kind::Rook, 3)),
+            ),
+            ("3k5/9/9/9/9/9/9/9/9/K8", SetupError::Square(Side::White, Kind::King, 0, 0)),
+            ("9/9/9/3k5/9/9/9/9/9/4K4", SetupError::Square(Side::Black, Kind::King, 6, 3)),
+            (
+                "3k5/9/9/9/9/9/9/9/3A5/4K4",
+                SetupError::Square(Side::White, Kind::Advisor, 1, 3),
+            ),
+            ("3k5/9/9/9/9/B8/9/9/9/4K4", SetupError::Square(Side::White, Kind::Bishop, 4, 0)),
+            // 相不能过河
+            ("3k5/9/9/2B6/9/9/9/9/9/4K4", SetupError::Square(Side::White, Kind::Bishop, 6, 2)),
+            ("3k5/9/9/9/9/9/9/9/9/2b1K4", SetupError::Square(Side::Black, Kind::Bishop, 0, 2)),
+            ("3k5/9/9/9/9/9/1P7/9/9/4K4", SetupError::Square(Side::White, Kind::Pawn, 3, 1)),
+            ("3k5/9/9/9/9/9/9/P8/9/4K4", SetupError::Square(Side::White, Kind::Pawn, 2, 0)),
+            ("3k5/p8/9/9/9/9/9/9/9/4K4", SetupError::Square(Side::Black, Kind::Pawn, 8, 0)),
+            ("4k4/9/9/9/9/9/9/9/9/4K4", SetupError::KingsFacing),
+            // 红车将军时不能轮到红方行棋
+            ("3k5/9/9/9/9/9/9/9/9/3RK4", SetupError::Checked(Side::Black)),
+        ];
+        for (placement, error) in cases {
+            assert_eq!(check(&board(placement), Side::White), Err(error), "{placement}");
+        }
+        // 中间有子时不算照面
+        assert_eq!(check(&board("4k4/9/9/9/9/9/9/9/4A4/4K4"), Side::White), Ok(()));
+        assert_eq!(check(&board("3k5/9/9/9/9/9/9/9/9/3RK4"), Side::Black), Ok(()));
+        assert_eq!(SetupError::KingsFacing.to_string(), "将帅不能照面");
+        assert_eq!(SetupError::Square(Side::White, Kind::Pawn, 3, 1).to_string(), "红兵不能在b3");
+    }
+
+    #[test]
+    fn test_editor() {
+        let mut editor = Editor {
+            broad_map: board(START_BOARD),
+            side: Side::White,
+            dragging: None,
+            broad: Entity::PLACEHOLDER,
+        };
+        editor.clear();
+        assert_eq!(check(&editor.broad_map, Side::White), Err(SetupError::KingsFacing));
+        editor.place(Piece::white(Kind::Advisor, 0, 0), 1, 4);
+        let advisor = editor.broad_map[1][4].unwrap();
+        assert_eq!((advisor.row, advisor.col), (1, 4));
+        assert_eq!(check(&editor.broad_map, Side::White), Ok(()));
+        assert_eq!(editor.broad_map.iter().flatten().flatten().count(), 3);
+    }
+}
@@ -0,0 +1,459 @@
+use bevy::prelude::*;
+
+use super::{ChessState, GameState};
+use crate::{
+    chess::inter::InterSession,
+    component::piece::Side,
+    game::{Data, GameMode},
+    net::{
+        discovery::{Advertiser, Browser},
+        Message, NetConnection, NetEvent, NET_PORT,
+    },
+    player::TIME_CONTROLS,
+    public,
+};
+
+// 联机大厅配置
+pub const LOBBY_NORMAL_BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+pub const LOBBY_HOVERED_BUTTON_COLOR: Color = Color::rgb(0.30, 0.30, 0.30);
+pub const LOBBY_PRESSED_BUTTON_COLOR: Color = Color::rgb(0.45, 0.45, 0.45);
+pub const LOBBY_HOST_TEXT: &str = "创建对局";
+pub const LOBBY_JOIN_TEXT: &str = "加入对局";
+pub const LOBBY_WATCH_TEXT: &str = "观战对局";
+pub const LOBBY_BACK_TEXT: &str = "返回";
+pub const LOBBY_GAMES_TEXT: &str = "局域网对局";
+pub const LOBBY_DEFAULT_STATUS: &str = "创建对局, 或选择局域网对局加入";
+// 最多显示的局域网对局数量
+pub const LOBBY_GAMES_MAX: usize = 4;
+// 加入对局的默认地址
+pub const LOBBY_DEFAULT_ADDRESS: &str = "127.0.0.1:9527";
+// 地址最大长度
+pub const LOBBY_ADDRESS_MAX_LEN: usize = 64;
+
+/// 联机大厅按钮
+#[derive(Component)]
+pub enum LobbyMenu {
+    // 创建对局, 等待对手加入
+    Host,
+    // 加入输入地址的对局
+    Join,
+    // 观战输入地址的对局
+    Watch,
+    // 加入局域网内发现的对局
+    Game(String),
+    // 观战局域网内已开局的对局
+    WatchGame(String),
+    // 断开连接, 返回主菜单
+    Back,
+}
+
+/// 大厅状态提示
+#[derive(Component)]
+pub struct LobbyStatus;
+
+/// 对局地址输入框
+#[derive(Component)]
+pub struct LobbyAddress;
+
+/// 局域网对局列表
+#[derive(Component)]
+pub struct LobbyGameList;
+
+/// 联机大厅: 创建对局, 选择局域网对局或输入地址加入对局
+pub fn spawn_lobby(commands: &mut Commands, fonts: &public::asset::Fonts) -> Entity {
+    // 进入大厅即开始搜索局域网对局
+    match Browser::new() {
+        Ok(browser) => commands.insert_resource(browser),
+        Err(err) => warn!("无法搜索局域网对局: {err}"),
+    }
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100_f32),
+                height: Val::Percent(100_f32),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    LOBBY_DEFAULT_STATUS,
+                    TextStyle {
+                        font: fonts.wenkai.clone(),
+                        font_size: 28_f32,
+                        color: Color::ANTIQUE_WHITE,
+                    },
+                )
+                .with_style(Style {
+                    top: Val::Percent(6_f32),
+                    position_type: PositionType::Absolute,
+                    ..default()
+                }),
+                LobbyStatus,
+            ));
+            make_lobby_button(
+                parent,
+                fonts.xiaoli.clone(),
+                LOBBY_HOST_TEXT,
+                LobbyMenu::Host,
+                14_f32,
+            );
+            // 地址输入框
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(360_f32),
+                        height: Val::Px(50_f32),
+                        top: Val::Percent(26_f32),
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        position_type: PositionType::Absolute,
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            LOBBY_DEFAULT_ADDRESS,
+                            TextStyle {
+                                font: fonts.wenkai.clone(),
+                                font_size: 28_f32,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        ),
+                        LobbyAddress,
+                    ));
+                });
+            make_lobby_button(
+                parent,
+                fonts.xiaoli.clone(),
+                LOBBY_JOIN_TEXT,
+                LobbyMenu::Join,
+                35_f32,
+            );
+            make_lobby_button(
+                parent,
+                fonts.xiaoli.clone(),
+                LOBBY_WATCH_TEXT,
+                LobbyMenu::Watch,
+                43_f32,
+            );
+            parent.spawn(
+                TextBundle::from_section(
+                    LOBBY_GAMES_TEXT,
+                    TextStyle {
+                        font: fonts.wenkai.clone(),
+                        font_size: 28_f32,
+                        color: Color::ANTIQUE_WHITE,
+                    },
+                )
+                .with_style(Style {
+                    top: Val::Percent(52_f32),
+                    position_type: PositionType::Absolute,
+                    ..default()
+                }),
+            );
+            // 由browse_system刷新列表内容
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        top: Val::Percent(57_f32),
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        position_type: PositionType::Absolute,
+                        ..default()
+                    },
+                    ..default()
+                },
+                LobbyGameList,
+            ));
+            make_lobby_button(
+                parent,
+                fonts.xiaoli.clone(),
+                LOBBY_BACK_TEXT,
+                LobbyMenu::Back,
+                87_f32,
+            );
+        })
+        .id()
+}
+
+pub fn in_state(
+    mut commands: Commands,
+    fonts: Res<public::asset::Fonts>,
+    data: Res<Data>,
+    mut entitys: ResMut<public::EntityResources>,
+    connection: Option<Res<NetConnection>>,
+    mut status_q: Query<&mut Text, (With<LobbyStatus>, Without<LobbyAddress>)>,
+    address_q: Query<&Text, (With<LobbyAddress>, Without<LobbyStatus>)>,
+    mut query: Query<
+        (&Interaction, &mut BackgroundColor, &LobbyMenu),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interact, mut color, menu) in &mut query {
+        match *interact {
+            Interaction::Pressed => {
+                *color = LOBBY_PRESSED_BUTTON_COLOR.into();
+                let status = match menu {
+                    LobbyMenu::Back => {
+                        commands.remove_resource::<NetConnection>();
+                        commands.remove_resource::<Advertiser>();
+                        commands.remove_resource::<Browser>();
+                        commands.entity(entitys.pending_menus.unwrap()).despawn_recursive();
+                        entitys.pending_menus =
+                            Some(super::pending::spawn_main_menu(&mut commands, &data, &fonts));
+                        continue;
+                    }
+                    _ if connection.is_some() => continue,
+                    // 默认端口被本机其他实例占用时使用随机端口, 由局域网广播告知对手
+                    LobbyMenu::Host => {
+                        match NetConnection::host(NET_PORT).or_else(|_| NetConnection::host(0)) {
+                            Ok(connection) => {
+                                let mut status = format!("等待对手加入, 端口: {}", connection.port);
+                                match Advertiser::new(connection.port, data.time_control.index()) {
+                                    Ok(advertiser) => commands.insert_resource(advertiser),
+                                    Err(err) => {
+                                        warn!("局域网广播失败: {err}");
+                                        status.push_str(", 局域网不可见");
+                                    }
+                                }
+                                commands.insert_resource(connection);
+                                status
+                            }
+                            Err(err) => format!("创建对局失败: {err}"),
+                        }
+                    }
+                    LobbyMenu::Join => {
+                        join(&mut commands, address_q.single().sections[0].value.clone())
+                    }
+                    LobbyMenu::Watch => {
+                        watch(&mut commands, address_q.single().sections[0].value.clone())
+                    }
+                    LobbyMenu::Game(address) => join(&mut commands, address.clone()),
+                    LobbyMenu::WatchGame(address) => watch(&mut commands, address.clone()),
+                };
+                info!("{status}");
+                for mut text in status_q.iter_mut() {
+                    text.sections[0].value = status.clone();
+                }
+            }
+            Interaction::Hovered => {
+                *color = LOBBY_HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *color = LOBBY_NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+fn join(commands: &mut Commands, address: String) -> String {
+    let status = format!("正在连接 {address}");
+    commands.insert_resource(NetConnection::join(address));
+    status
+}
+
+fn watch(commands: &mut Commands, address: String) -> String {
+    let status = format!("正在连接 {address} 观战");
+    commands.insert_resource(NetConnection::watch(address));
+    status
+}
+
+/// 定时搜索局域网对局, 列表变化时重建对局按钮
+pub fn browse_system(
+    mut commands: Commands,
+    fonts: Res<public::asset::Fonts>,
+    mut browser: ResMut<Browser>,
+    list_q: Query<Entity, With<LobbyGameList>>,
+) {
+    if !browser.poll() {
+        return;
+    }
+    let list = match list_q.get_single() {
+        Ok(list) => list,
+        Err(_) => return,
+    };
+    commands.entity(list).despawn_descendants();
+    commands.entity(list).with_children(|parent| {
+        for game in browser.games().iter().take(LOBBY_GAMES_MAX) {
+            let time_control = TIME_CONTROLS[game.time_control].name;
+            // 已开局的对局只能观战
+            let (text, menu) = match game.playing {
+                true => (
+                    format!("{}  {}  观战", game.addr, time_control),
+                    LobbyMenu::WatchGame(game.addr.to_string()),
+                ),
+                false => (
+                    format!("{}  {}", game.addr, time_control),
+                    LobbyMenu::Game(game.addr.to_string()),
+                ),
+            };
+            make_game_button(parent, fonts.wenkai.clone(), text, menu);
+        }
+    });
+}
+
+/// 地址输入: 接收键盘字符, 退格删除
+pub fn address_input(
+    keys: Res<Input<KeyCode>>,
+    mut chars: EventReader<ReceivedCharacter>,
+    mut address_q: Query<&mut Text, With<LobbyAddress>>,
+) {
+    let mut text = match address_q.get_single_mut() {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+    let address = &mut text.sections[0].value;
+    if keys.just_pressed(KeyCode::Back) {
+        address.pop();
+    }
+    for event in chars.iter() {
+        let ch = event.char;
+        if (ch.is_ascii_alphanumeric() || ".:-[]".contains(ch))
+            && address.len() < LOBBY_ADDRESS_MAX_LEN
+        {
+            address.push(ch);
+        }
+    }
+}
+
+/// 等待连接结果, 连接建立后由主场方决定执棋方并开局, 观众等待开局后观战
+pub fn connect_system(
+    mut commands: Commands,
+    connection: Res<NetConnection>,
+    advertiser: Option<Res<Advertiser>>,
+    mut data: ResMut<Data>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut chess_state: ResMut<NextState<ChessState>>,
+    mut status_q: Query<&mut Text, With<LobbyStatus>>,
+) {
+    while let Some(event) = connection.poll() {
+        let (side, time_control) = match event {
+            // 主场方执红, 告知客场方执黑与时限规则
+            NetEvent::Connected if connection.host => {
+                // 对局已满, 局域网广播改为供观众加入
+                if let Some(advertiser) = &advertiser {
+                    advertiser.set_playing();
+                }
+                let time_control = data.time_control.index();
+                connection.send(Message::Start(Side::Black, time_control));
+                // 在大厅中等待开局的观众
+                connection.broadcast(Message::Start(Side::White, time_control));
+                (Some(Side::White), time_control)
+            }
+            NetEvent::Message(Message::Start(side, time_control)) if !connection.host => {
+                match connection.spectator {
+                    true => (None, time_control),
+                    false => (Some(side), time_control),
+                }
+            }
+            NetEvent::Disconnected => {
+                let status = match connection.host {
+                    true => "对手已离开",
+                    false => "连接失败",
+                };
+                info!("{status}");
+                for mut text in status_q.iter_mut() {
+                    text.sections[0].value = String::from(status);
+                }
+                commands.remove_resource::<NetConnection>();
+                commands.remove_resource::<Advertiser>();
+                return;
+            }
+            _ => continue,
+        };
+        commands.remove_resource::<Browser>();
+        data.set_time_control(TIME_CONTROLS[time_control]);
+        match side {
+            Some(side) => {
+                info!("联机开局 本方执{}", side.name());
+                commands.insert_resource(InterSession::default());
+                data.mode = Some(GameMode::InterGame);
+                data.set_home_side(side);
+            }
+            None => {
+                info!("开始观战");
+                data.mode = Some(GameMode::WatchGame);
+                data.set_spectator();
+            }
+        }
+        data.current_side = Some(Side::White);
+        data.engine.from_fen(public::START_POS);
+        game_state.set(GameState::RUNNING);
+        chess_state.set(data.chess_state());
+        return;
+    }
+}
+
+fn make_lobby_button(
+    parent: &mut ChildBuilder,
+    font: Handle<Font>,
+    text: &str,
+    menu: LobbyMenu,
+    top_px: f32,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(204_f32),
+                    height: Val::Px(65_f32),
+                    top: Val::Percent(top_px),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    align_content: AlignContent::Center,
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                background_color: LOBBY_NORMAL_BUTTON_COLOR.into(),
+                ..default()
+            },
+            menu,
+        ))
+        .with_children(|text_parent| {
+            text_parent.spawn(TextBundle::from_section(
+                text,
+                TextStyle {
+                    font,
+                    font_size: 40.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            ));
+        });
+}
+
+fn make_game_button(parent: &mut ChildBuilder, font: Handle<Font>, text: String, menu: LobbyMenu) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(420_f32),
+                    height: Val::Px(48_f32),
+                    margin: UiRect::all(Val::Px(4_f32)),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: LOBBY_NORMAL_BUTTON_COLOR.into(),
+                ..default()
+            },
+            menu,
+        ))
+        .with_children(|text_parent| {
+            text_parent.spawn(TextBundle::from_section(
+                text,
+                TextStyle {
+                    font,
+                    font_size: 26.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            ));
+        });
+}
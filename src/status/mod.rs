@@ -1,6 +1,8 @@
 use bevy::{input::keyboard::KeyboardInput, prelude::*};
 
+pub mod editor;
 pub mod exited;
+pub mod lobby;
 pub mod paused;
 pub mod pending;
 pub mod running;
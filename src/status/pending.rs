@@ -1,11 +1,19 @@
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use bevy::prelude::*;
 
 use super::{ChessState, GameState};
 use crate::{
+    chess::position::{self, PositionDialog},
     component::piece::Side,
+    event::{EventAction, GameChangeEvent},
     game::{Data, GameMode},
-    player::Id,
-    public,
+    pgn,
+    player::AiLevel,
+    public, xqf,
 };
 
 // 主菜单配置
@@ -15,8 +23,18 @@ pub const PENDING_MANU_PRESSED_BUTTON_COLOR: Color = Color::rgb(0.45, 0.45, 0.45
 pub const PENDING_MENU_AI_GAME_TEXT: &str = "人机对弈";
 pub const PENDING_MENU_INTER_GAME_TEXT: &str = "联机对弈";
 pub const PENDING_MENU_DEDUCE_GAME_TEXT: &str = "打谱推演";
+pub const PENDING_MENU_LOAD_POSITION_TEXT: &str = "载入局面";
+pub const PENDING_MENU_SETUP_BOARD_TEXT: &str = "摆棋";
 pub const PENDING_MENU_SETTING_GAME_TEXT: &str = "系统设置";
 pub const PENDING_MENU_EXIT_GAME_TEXT: &str = "退出游戏";
+pub const PENDING_MENU_BACK_TEXT: &str = "返回";
+pub const PENDING_MENU_BLANK_DEDUCE_TEXT: &str = "新建打谱";
+// 打谱子菜单列出的最近棋谱数
+pub const PENDING_MENU_SAVED_GAMES: usize = 4;
+
+// AI难度子菜单
+pub const AI_LEVELS: [AiLevel; 4] =
+    [AiLevel::Beginner, AiLevel::Amateur, AiLevel::Expert, AiLevel::Master];
 
 /// MainMenu 游戏全局菜单, ESC呼出, 位于界面中央
 #[derive(Component)]
@@ -25,21 +43,50 @@ pub enum PendingMenu {
     NewAiGame,
     // 打谱推演
     NewDeduceGame,
+    // 从开局局面打谱
+    BlankDeduceGame,
+    // 打开保存的棋谱打谱
+    OpenGame(PathBuf),
+    // 输入FEN载入局面打谱
+    LoadPosition,
+    // 摆放棋子后开局
+    SetupBoard,
     // 联机对弈
     NewInterGame,
+    // 时限规则, 点击切换
+    TimeControl,
     // 系统设置
     SettingGame,
     // 退出游戏
     ExitGame,
+    // 选择AI难度开始人机对弈
+    AiLevel(AiLevel),
+    // 返回主菜单
+    Back,
 }
 
 pub fn enter_state(
     mut commands: Commands,
+    data: Res<Data>,
     fonts: Res<public::asset::Fonts>,
     // mut entitys: ResMut<public::EntityResources>,
 ) {
     info!("进入PENDING");
-    let menus = commands
+    let menus = spawn_main_menu(&mut commands, &data, &fonts);
+    commands.insert_resource(public::EntityResources {
+        pending_menus: Some(menus),
+        paused_menus: None,
+        chessbroad: None,
+        selected: None,
+    });
+}
+
+pub fn spawn_main_menu(
+    commands: &mut Commands,
+    data: &Data,
+    fonts: &public::asset::Fonts,
+) -> Entity {
+    commands
         .spawn(NodeBundle {
             style: Style {
                 position_type: PositionType::Absolute,
@@ -57,80 +104,286 @@ pub fn enter_state(
                 fonts.xiaoli.clone(),
                 PENDING_MENU_AI_GAME_TEXT,
                 PendingMenu::NewAiGame,
-                16_f32,
+                12_f32,
             );
             make_main_menu_text_bundle(
                 parent,
                 fonts.xiaoli.clone(),
                 PENDING_MENU_INTER_GAME_TEXT,
                 PendingMenu::NewInterGame,
-                26_f32,
+                22_f32,
             );
             make_main_menu_text_bundle(
                 parent,
                 fonts.xiaoli.clone(),
                 PENDING_MENU_DEDUCE_GAME_TEXT,
                 PendingMenu::NewDeduceGame,
-                36_f32,
+                32_f32,
+            );
+            make_main_menu_text_bundle(
+                parent,
+                fonts.xiaoli.clone(),
+                PENDING_MENU_LOAD_POSITION_TEXT,
+                PendingMenu::LoadPosition,
+                42_f32,
+            );
+            make_main_menu_text_bundle(
+                parent,
+                fonts.xiaoli.clone(),
+                PENDING_MENU_SETUP_BOARD_TEXT,
+                PendingMenu::SetupBoard,
+                52_f32,
+            );
+            make_main_menu_text_bundle(
+                parent,
+                fonts.xiaoli.clone(),
+                data.time_control.name,
+                PendingMenu::TimeControl,
+                62_f32,
             );
             make_main_menu_text_bundle(
                 parent,
                 fonts.xiaoli.clone(),
                 PENDING_MENU_SETTING_GAME_TEXT,
                 PendingMenu::SettingGame,
-                46_f32,
+                72_f32,
             );
             make_main_menu_text_bundle(
                 parent,
                 fonts.xiaoli.clone(),
                 PENDING_MENU_EXIT_GAME_TEXT,
                 PendingMenu::ExitGame,
-                56_f32,
+                82_f32,
             );
         })
-        .id();
-    commands.insert_resource(public::EntityResources {
-        pending_menus: Some(menus),
-        paused_menus: None,
-        chessbroad: None,
-        selected: None,
-    });
+        .id()
+}
+
+/// 人机对弈的难度子菜单
+fn spawn_level_menu(commands: &mut Commands, fonts: &public::asset::Fonts) -> Entity {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100_f32),
+                height: Val::Percent(100_f32),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for (i, level) in AI_LEVELS.iter().enumerate() {
+                make_main_menu_text_bundle(
+                    parent,
+                    fonts.xiaoli.clone(),
+                    level.name(),
+                    PendingMenu::AiLevel(*level),
+                    16_f32 + 10_f32 * i as f32,
+                );
+            }
+            make_main_menu_text_bundle(
+                parent,
+                fonts.xiaoli.clone(),
+                PENDING_MENU_BACK_TEXT,
+                PendingMenu::Back,
+                16_f32 + 10_f32 * AI_LEVELS.len() as f32,
+            );
+        })
+        .id()
+}
+
+/// 棋谱目录中最近的count个PGN或XQF棋谱
+fn saved_games(count: usize) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(public::path::GAMES_DIR) else {
+        return Vec::new();
+    };
+    let mut games: Vec<(SystemTime, PathBuf)> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension().is_some_and(|ext| {
+                ext.eq_ignore_ascii_case(pgn::PGN_EXTENSION)
+                    || ext.eq_ignore_ascii_case(xqf::XQF_EXTENSION)
+            })
+        })
+        .map(|path| {
+            let modified =
+                fs::metadata(&path).and_then(|meta| meta.modified()).unwrap_or(UNIX_EPOCH);
+            (modified, path)
+        })
+        .collect();
+    games.sort_by_key(|(modified, _)| Reverse(*modified));
+    games.into_iter().take(count).map(|(_, path)| path).collect()
+}
+
+/// 按扩展名打开PGN或XQF棋谱
+fn open_game(path: &Path) -> Result<Data, String> {
+    match path.extension() {
+        Some(ext) if ext.eq_ignore_ascii_case(xqf::XQF_EXTENSION) => {
+            xqf::open(path).map_err(|err| err.to_string())
+        }
+        _ => pgn::open(path).map_err(|err| err.to_string()),
+    }
+}
+
+/// 打谱子菜单: 新建打谱或打开最近保存的棋谱
+fn spawn_deduce_menu(commands: &mut Commands, fonts: &public::asset::Fonts) -> Entity {
+    let games = saved_games(PENDING_MENU_SAVED_GAMES);
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100_f32),
+                height: Val::Percent(100_f32),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            make_main_menu_text_bundle(
+                parent,
+                fonts.xiaoli.clone(),
+                PENDING_MENU_BLANK_DEDUCE_TEXT,
+                PendingMenu::BlankDeduceGame,
+                16_f32,
+            );
+            for (i, path) in games.iter().enumerate() {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                make_main_menu_text_bundle(
+                    parent,
+                    fonts.wenkai.clone(),
+                    &name,
+                    PendingMenu::OpenGame(path.clone()),
+                    26_f32 + 10_f32 * i as f32,
+                );
+            }
+            make_main_menu_text_bundle(
+                parent,
+                fonts.xiaoli.clone(),
+                PENDING_MENU_BACK_TEXT,
+                PendingMenu::Back,
+                26_f32 + 10_f32 * games.len() as f32,
+            );
+        })
+        .id()
 }
 
 pub fn in_state(
+    mut commands: Commands,
+    fonts: Res<public::asset::Fonts>,
+    image_handles: Res<public::asset::Images>,
+    piece_handles: Res<public::asset::Pieces>,
+    mut entitys: ResMut<public::EntityResources>,
     mut game_state: ResMut<NextState<GameState>>,
     mut chess_state: ResMut<NextState<ChessState>>,
     mut data: ResMut<Data>,
     mut query: Query<
-        (&Interaction, &mut BackgroundColor, &PendingMenu),
+        (&Interaction, &mut BackgroundColor, &PendingMenu, &Children),
         (Changed<Interaction>, With<Button>),
     >,
+    mut text_q: Query<&mut Text>,
 ) {
-    for (interact, mut color, menu) in &mut query {
+    for (interact, mut color, menu, children) in &mut query {
         match *interact {
             Interaction::Pressed => {
                 *color = PENDING_MANU_PRESSED_BUTTON_COLOR.into();
                 match menu {
                     PendingMenu::NewAiGame => {
-                        info!("todo NewAiGame");
+                        // 先选择难度
+                        commands.entity(entitys.pending_menus.unwrap()).despawn_recursive();
+                        entitys.pending_menus = Some(spawn_level_menu(&mut commands, &fonts));
+                    }
+
+                    PendingMenu::AiLevel(level) => {
+                        info!("NewAiGame 难度: {}", level.name());
                         data.mode = Some(GameMode::AiGame);
-                        data.ai_side = Some(Side::Black);
-                        data.black_player.id = Id::Ai;
-                        data.white_player.id = Id::Home;
+                        data.ai_level = *level;
+                        data.set_ai_side(Side::Black);
                         data.current_side = Some(Side::White);
                         data.engine.from_fen(public::START_POS);
                         game_state.set(GameState::RUNNING);
-                        chess_state.set(ChessState::HomePlay);
+                        chess_state.set(data.chess_state());
                     }
 
                     PendingMenu::NewDeduceGame => {
-                        info!("todo NewDeduceGame");
+                        // 先选择新建打谱或打开棋谱
+                        commands.entity(entitys.pending_menus.unwrap()).despawn_recursive();
+                        entitys.pending_menus = Some(spawn_deduce_menu(&mut commands, &fonts));
+                    }
+
+                    PendingMenu::BlankDeduceGame => {
+                        // 一人执双方行棋
+                        info!("NewDeduceGame");
+                        data.mode = Some(GameMode::DeduceGame);
+                        data.current_side = Some(Side::White);
+                        data.engine.from_fen(public::START_POS);
                         game_state.set(GameState::RUNNING);
+                        chess_state.set(data.chess_state());
+                    }
+
+                    PendingMenu::OpenGame(path) => match open_game(path) {
+                        Ok(mut game) => {
+                            info!("打开棋谱 {} 共{}步", path.display(), game.history.len());
+                            game.mode = Some(GameMode::DeduceGame);
+                            game.set_time_control(data.time_control);
+                            game.notation = data.notation;
+                            *data = game;
+                            game_state.set(GameState::RUNNING);
+                            chess_state.set(data.chess_state());
+                        }
+                        Err(err) => {
+                            warn!("打开棋谱失败 {}: {err}", path.display());
+                            if let Ok(mut text) = text_q.get_mut(children[0]) {
+                                text.sections[0].value = err.to_string();
+                                text.sections[0].style.font_size = 16_f32;
+                            }
+                        }
+                    },
+
+                    PendingMenu::LoadPosition => {
+                        // 由局面弹窗载入后开始打谱
+                        info!("LoadPosition");
+                        commands.entity(entitys.pending_menus.unwrap()).despawn_recursive();
+                        entitys.pending_menus = Some(position::spawn_popup(
+                            &mut commands,
+                            &fonts,
+                            &image_handles,
+                            false,
+                        ));
+                        commands.init_resource::<PositionDialog>();
+                    }
+
+                    PendingMenu::SetupBoard => {
+                        // 进入摆棋界面, 由编辑器开局
+                        info!("SetupBoard");
+                        commands.entity(entitys.pending_menus.unwrap()).despawn_recursive();
+                        entitys.pending_menus = Some(super::editor::spawn_editor(
+                            &mut commands,
+                            &fonts,
+                            &image_handles,
+                            &piece_handles,
+                        ));
                     }
 
                     PendingMenu::NewInterGame => {
-                        info!("todo NewDeduceGame");
-                        game_state.set(GameState::RUNNING);
+                        // 进入联机大厅, 连接建立后开局
+                        info!("NewInterGame");
+                        commands.entity(entitys.pending_menus.unwrap()).despawn_recursive();
+                        entitys.pending_menus =
+                            Some(super::lobby::spawn_lobby(&mut commands, &fonts));
+                    }
+
+                    PendingMenu::TimeControl => {
+                        let time_control = data.time_control.next();
+                        info!("时限规则: {}", time_control.name);
+                        data.set_time_control(time_control);
+                        if let Ok(mut text) = text_q.get_mut(children[0]) {
+                            text.sections[0].value = time_control.name.to_string();
+                        }
                     }
 
                     PendingMenu::SettingGame => {
@@ -138,6 +391,11 @@ pub fn in_state(
                         game_state.set(GameState::RUNNING);
                     }
 
+                    PendingMenu::Back => {
+                        commands.entity(entitys.pending_menus.unwrap()).despawn_recursive();
+                        entitys.pending_menus = Some(spawn_main_menu(&mut commands, &data, &fonts));
+                    }
+
                     PendingMenu::ExitGame => {
                         info!("ExitGame");
                         game_state.set(GameState::EXITED);
@@ -154,6 +412,21 @@ pub fn in_state(
     }
 }
 
+/// 从暂停菜单返回主页: 销毁当前对局
+pub fn from_paused_enter(
+    mut data: ResMut<Data>,
+    mut chess_state: ResMut<NextState<ChessState>>,
+    mut event: EventWriter<GameChangeEvent>,
+) {
+    info!("paused to pending");
+    // 保留主菜单选择的时限规则
+    let time_control = data.time_control;
+    *data = Data::new();
+    data.set_time_control(time_control);
+    chess_state.set(ChessState::None);
+    event.send(GameChangeEvent(EventAction::Despawn));
+}
+
 pub fn exit_state(mut commands: Commands, entitys: Res<public::EntityResources>) {
     info!("退出PENDING");
     commands.entity(entitys.pending_menus.unwrap()).despawn_recursive();
@@ -166,6 +439,12 @@ fn make_main_menu_text_bundle(
     menu: PendingMenu,
     top_px: f32,
 ) {
+    // 时限规则与棋谱文件名较长, 使用小一号字体
+    let font_size = match menu {
+        PendingMenu::TimeControl => 32_f32,
+        PendingMenu::OpenGame(_) => 20_f32,
+        _ => 40_f32,
+    };
     parent
         .spawn((
             ButtonBundle {
@@ -189,7 +468,7 @@ fn make_main_menu_text_bundle(
                 text,
                 TextStyle {
                     font,
-                    font_size: 40.0,
+                    font_size,
                     color: Color::rgb(0.9, 0.9, 0.9),
                 },
             ));
@@ -0,0 +1,459 @@
+//! 象棋演播室(XQF)棋谱的读取
+//!
+//! 文件头1024字节, 之后是前序排列的走法记录; 10版及以前不加密, 11版起棋子位置与走法加密,
+//! 走法记录再按32字节的密钥流加密, 12版起棋子位置另有轮换。文字为GBK编码
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use bevy::prelude::*;
+use chessai::position::pos2iccs;
+
+use crate::component::piece::{Kind, Piece, Side};
+use crate::fen;
+use crate::game::Data;
+use crate::notation::BroadMap;
+use crate::public::Pos;
+
+pub const XQF_EXTENSION: &str = "xqf";
+
+const HEADER_LEN: usize = 1024;
+const COPYRIGHT: &[u8; 32] = b"[(C) Copyright Mr. Dong Shiwei.]";
+// 文件头中32个棋子的顺序, 前16个为红方, 后16个为黑方
+const PIECE_KINDS: [Kind; 16] = [
+    Kind::Rook,
+    Kind::Knight,
+    Kind::Bishop,
+    Kind::Advisor,
+    Kind::King,
+    Kind::Advisor,
+    Kind::Bishop,
+    Kind::Knight,
+    Kind::Rook,
+    Kind::Cannon,
+    Kind::Cannon,
+    Kind::Pawn,
+    Kind::Pawn,
+    Kind::Pawn,
+    Kind::Pawn,
+    Kind::Pawn,
+];
+
+/// 读取错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XqfError {
+    // 读取文件失败
+    Io(String),
+    // 不是XQF文件
+    Signature,
+    // 文件在某个偏移处意外结束
+    Truncated(usize),
+    // 开局局面错误
+    Position(String),
+    // 某个偏移处的走法记录错误
+    Record(usize),
+    // 主线第几步不合法
+    Move(usize, String),
+}
+
+impl fmt::Display for XqfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XqfError::Io(err) => write!(f, "{err}"),
+            XqfError::Signature => write!(f, "不是XQF棋谱"),
+            XqfError::Truncated(offset) => write!(f, "棋谱不完整: 偏移{offset}"),
+            XqfError::Position(text) => write!(f, "开局局面错误: {text}"),
+            XqfError::Record(offset) => write!(f, "走法记录错误: 偏移{offset}"),
+            XqfError::Move(serial, iccs) => write!(f, "第{serial}步走法不合法: {iccs}"),
+        }
+    }
+}
+
+/// 走法树的节点, 第一个后续为主线, 其余为变着
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub src: Pos,
+    pub dst: Pos,
+    pub comment: String,
+    pub children: Vec<Node>,
+}
+
+/// XQF棋谱
+#[derive(Debug, Clone)]
+pub struct Xqf {
+    pub version: u8,
+    pub title: String,
+    pub event: String,
+    pub date: String,
+    pub site: String,
+    pub red: String,
+    pub black: String,
+    pub annotator: String,
+    pub author: String,
+    // 与PGN相同: 1-0 / 0-1 / 1/2-1/2 / *
+    pub result: &'static str,
+    // 开局局面
+    pub broad_map: BroadMap,
+    // 先行方, 由第一步走法确定
+    pub side: Side,
+    // 开局前的注释
+    pub comment: String,
+    // 第一步的各个走法
+    pub moves: Vec<Node>,
+}
+
+/// 解密参数
+struct Keys {
+    piece: u8,
+    src: u8,
+    dst: u8,
+    comment: i32,
+    stream: [u8; 32],
+}
+
+impl Keys {
+    fn new(header: &[u8]) -> Self {
+        let version = header[2];
+        let mut keys = Keys {
+            piece: 0,
+            src: 0,
+            dst: 0,
+            comment: 0,
+            stream: [0; 32],
+        };
+        if version < 11 {
+            return keys;
+        }
+        let square = |key: u8| (key as u32 * key as u32 * 54 + 221) as u8;
+        keys.piece = square(header[13]).wrapping_mul(header[13]);
+        keys.src = square(header[14]).wrapping_mul(keys.piece);
+        keys.dst = square(header[15]).wrapping_mul(keys.src);
+        keys.comment = ((header[12] as i32 * 256 + header[13] as i32) % 32000) + 767;
+        // 11版起走法记录按密钥流加密, 12版起棋子位置另有轮换
+        let mask = header[3];
+        let args: Vec<u8> = (0..4).map(|i| (header[12 + i] & mask) | header[8 + i]).collect();
+        for (i, key) in keys.stream.iter_mut().enumerate() {
+            *key = COPYRIGHT[i] & args[i % 4];
+        }
+        keys
+    }
+}
+
+/// 按密钥流解密的走法记录读取
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    keys: Keys,
+    version: u8,
+}
+
+impl<'a> Reader<'a> {
+    fn read(&mut self, len: usize) -> Result<Vec<u8>, XqfError> {
+        let end = self.offset + len;
+        let bytes = self.bytes.get(self.offset..end).ok_or(XqfError::Truncated(self.offset))?;
+        let bytes = bytes
+            .iter()
+            .enumerate()
+            .map(|(i, b)| b.wrapping_sub(self.keys.stream[(self.offset + i) % 32]))
+            .collect();
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn read_int(&mut self) -> Result<i32, XqfError> {
+        let bytes = self.read(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// 读取一条走法记录: 起点、落点、是否有后续、是否有变着、注释
+    fn record(&mut self) -> Result<(u8, u8, bool, bool, String), XqfError> {
+        let offset = self.offset;
+        let bytes = self.read(4)?;
+        let src = bytes[0].wrapping_sub(0x18).wrapping_sub(self.keys.src);
+        let dst = bytes[1].wrapping_sub(0x20).wrapping_sub(self.keys.dst);
+        let (next, variation, len) = match self.version {
+            // 旧版高4位表示有后续, 低4位表示有变着, 每条记录都有注释长度
+            ..=10 => (bytes[2] & 0xF0 != 0, bytes[2] & 0x0F != 0, self.read_int()?),
+            _ => {
+                let len = match bytes[2] & 0x20 {
+                    0 => 0,
+                    _ => self.read_int()? - self.keys.comment,
+                };
+                (bytes[2] & 0x80 != 0, bytes[2] & 0x40 != 0, len)
+            }
+        };
+        if len < 0 {
+            return Err(XqfError::Record(offset));
+        }
+        let comment = decode_text(&self.read(len as usize)?);
+        Ok((src, dst, next, variation, comment))
+    }
+
+    /// 读取一组同一局面下的走法, 每个走法的后续先于其变着
+    fn nodes(&mut self) -> Result<Vec<Node>, XqfError> {
+        let mut nodes = Vec::new();
+        loop {
+            let offset = self.offset;
+            let (src, dst, next, variation, comment) = self.record()?;
+            let (src, dst) = match (square(src), square(dst)) {
+                (Some(src), Some(dst)) => (src, dst),
+                _ => return Err(XqfError::Record(offset)),
+            };
+            let children = match next {
+                true => self.nodes()?,
+                false => Vec::new(),
+            };
+            nodes.push(Node {
+                src,
+                dst,
+                comment,
+                children,
+            });
+            if !variation {
+                return Ok(nodes);
+            }
+        }
+    }
+}
+
+/// 位置编码为 纵线*10+横线, 横线0为红方底线
+fn square(value: u8) -> Option<Pos> {
+    match value {
+        0..=89 => Some(Pos::new(value as usize % 10, value as usize / 10)),
+        _ => None,
+    }
+}
+
+fn decode_text(bytes: &[u8]) -> String {
+    let (text, _) = encoding_rs::GBK.decode_without_bom_handling(bytes);
+    text.trim_end_matches('\0').to_string()
+}
+
+/// 文件头中首字节为长度的字符串
+fn header_text(header: &[u8], offset: usize, size: usize) -> String {
+    let len = (header[offset] as usize).min(size - 1);
+    decode_text(&header[offset + 1..offset + 1 + len])
+}
+
+/// 解析XQF棋谱
+pub fn parse(bytes: &[u8]) -> Result<Xqf, XqfError> {
+    if bytes.len() < 2 || &bytes[..2] != b"XQ" {
+        return Err(XqfError::Signature);
+    }
+    let header = bytes.get(..HEADER_LEN).ok_or(XqfError::Truncated(bytes.len()))?;
+    let version = header[2];
+    let keys = Keys::new(header);
+
+    // 12版起棋子位置的顺序也经过轮换
+    let mut positions = [0_u8; 32];
+    for (i, value) in header[16..48].iter().enumerate() {
+        let index = match version {
+            ..=11 => i,
+            _ => (keys.piece as usize + 1 + i) % 32,
+        };
+        positions[index] = value.wrapping_sub(keys.piece);
+    }
+    let mut broad_map: BroadMap = Default::default();
+    for (i, value) in positions.iter().enumerate() {
+        // 被吃掉的棋子位置超出棋盘
+        let Some(pos) = square(*value) else {
+            continue;
+        };
+        if broad_map[pos.row][pos.col].is_some() {
+            let square = format!("{}{}", (b'a' + pos.col as u8) as char, pos.row);
+            return Err(XqfError::Position(format!("棋子重叠 {square}")));
+        }
+        let kind = PIECE_KINDS[i % 16];
+        broad_map[pos.row][pos.col] = Some(match i < 16 {
+            true => Piece::white(kind, pos.row, pos.col),
+            false => Piece::black(kind, pos.row, pos.col),
+        });
+    }
+    fen::validate(&broad_map).map_err(|err| XqfError::Position(err.to_string()))?;
+
+    let mut reader = Reader {
+        bytes,
+        offset: HEADER_LEN,
+        keys,
+        version,
+    };
+    // 第一条记录只保存开局前的注释
+    let (_, _, next, _, comment) = reader.record()?;
+    let moves = match next {
+        true => reader.nodes()?,
+        false => Vec::new(),
+    };
+    let side = moves
+        .first()
+        .and_then(|node| broad_map[node.src.row][node.src.col])
+        .map_or(Side::White, |piece| piece.side);
+
+    Ok(Xqf {
+        version,
+        title: header_text(header, 80, 64),
+        event: header_text(header, 208, 64),
+        date: header_text(header, 272, 16),
+        site: header_text(header, 288, 16),
+        red: header_text(header, 304, 16),
+        black: header_text(header, 320, 16),
+        annotator: header_text(header, 464, 16),
+        author: header_text(header, 480, 16),
+        result: match header[51] {
+            1 => "1-0",
+            2 => "0-1",
+            3 => "1/2-1/2",
+            _ => "*",
+        },
+        broad_map,
+        side,
+        comment,
+        moves,
+    })
+}
+
+/// 由棋谱主线重建对局, 变着与注释不载入
+pub fn load(xqf: &Xqf) -> Result<Data, XqfError> {
+    info!(
+        "XQF{}版 {} {} {} {} 红方:{} 黑方:{} {} 评注:{} 作者:{}",
+        xqf.version,
+        xqf.title,
+        xqf.event,
+        xqf.date,
+        xqf.site,
+        xqf.red,
+        xqf.black,
+        xqf.result,
+        xqf.annotator,
+        xqf.author
+    );
+    if !xqf.comment.is_empty() {
+        info!("开局 {}", xqf.comment);
+    }
+    let mut data = Data::new();
+    data.set_position(&fen::Position::new(xqf.broad_map, xqf.side));
+    let mut nodes = &xqf.moves;
+    while let Some(node) = nodes.first() {
+        if !data.try_move(node.src, node.dst) {
+            let iccs = pos2iccs(node.src.row, node.src.col, node.dst.row, node.dst.col);
+            return Err(XqfError::Move(data.history.len() + 1, iccs));
+        }
+        if !node.comment.is_empty() {
+            info!("第{}步 {}", data.history.len(), node.comment);
+        }
+        nodes = &node.children;
+    }
+    Ok(data)
+}
+
+/// 读取并载入XQF棋谱文件
+pub fn open(path: &Path) -> Result<Data, XqfError> {
+    let bytes = fs::read(path).map_err(|err| XqfError::Io(err.to_string()))?;
+    load(&parse(&bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: &[u8] = include_bytes!("../tests/fixtures/plain.xqf");
+    const KEYED: &[u8] = include_bytes!("../tests/fixtures/keyed.xqf");
+    const ENCRYPTED: &[u8] = include_bytes!("../tests/fixtures/encrypted.xqf");
+    const ENDGAME: &[u8] = include_bytes!("../tests/fixtures/endgame.xqf");
+
+    fn iccs(src: Pos, dst: Pos) -> String {
+        pos2iccs(src.row, src.col, dst.row, dst.col)
+    }
+
+    /// 走法树按前序写成 走法(注释)[变着...]
+    fn tree(nodes: &[Node]) -> String {
+        nodes
+            .iter()
+            .map(|node| {
+                let mut text = iccs(node.src, node.dst);
+                if !node.comment.is_empty() {
+                    text.push_str(&format!("({})", node.comment));
+                }
+                if !node.children.is_empty() {
+                    text.push_str(&format!("[{}]", tree(&node.children)));
+                }
+                text
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn test_versions() {
+        // 同一局棋的不加密、11版加密与12版起加密版本
+        assert!(Keys::new(PLAIN).stream.iter().all(|key| *key == 0));
+        assert!(Keys::new(KEYED).stream.iter().any(|key| *key != 0));
+        for (bytes, version) in [(PLAIN, 10), (KEYED, 11), (ENCRYPTED, 18)] {
+            let xqf = parse(bytes).unwrap();
+            assert_eq!(xqf.version, version);
+            assert_eq!(xqf.title, "测试对局");
+            assert_eq!(xqf.event, "全国象棋个人赛");
+            assert_eq!(xqf.date, "2026.10.18");
+            assert_eq!(xqf.site, "北京");
+            assert_eq!(xqf.red, "许银川");
+            assert_eq!(xqf.black, "吕钦");
+            assert_eq!(xqf.annotator, "象棋巫师");
+            assert_eq!(xqf.author, "");
+            assert_eq!(xqf.result, "1-0");
+            assert_eq!(xqf.side, Side::White);
+            assert_eq!(xqf.comment, "开局");
+            assert_eq!(
+                tree(&xqf.moves),
+                "h2e2(中炮开局)[h9g7[h0g2[i9h9(出车) i9i8(横车)]] b9c7(反宫马)[c3c4]]"
+            );
+            assert_eq!(
+                fen::serialize(&fen::Position::new(xqf.broad_map, xqf.side)),
+                "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1"
+            );
+        }
+    }
+
+    #[test]
+    fn test_endgame() {
+        // 黑方先行的残局, 缺少的棋子位置超出棋盘
+        let xqf = parse(ENDGAME).unwrap();
+        assert_eq!(xqf.title, "残局");
+        assert_eq!(xqf.result, "0-1");
+        assert_eq!(xqf.side, Side::Black);
+        assert_eq!(xqf.broad_map.iter().flatten().flatten().count(), 6);
+        assert_eq!(
+            fen::serialize(&fen::Position::new(xqf.broad_map, xqf.side)),
+            "3k2r2/4P4/9/9/9/9/9/9/4A4/3AK4 b - - 0 1"
+        );
+        assert_eq!(tree(&xqf.moves), "g9g1[e1f2]");
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |bytes: &[u8]| parse(bytes).unwrap_err();
+        assert_eq!(error(b"XP"), XqfError::Signature);
+        assert_eq!(error(&PLAIN[..512]), XqfError::Truncated(512));
+        let len = ENCRYPTED.len() - 3;
+        assert!(matches!(error(&ENCRYPTED[..len]), XqfError::Truncated(_)));
+
+        // 去掉红帅
+        let mut bytes = PLAIN.to_vec();
+        bytes[16 + 4] = 0xFF;
+        assert_eq!(error(&bytes), XqfError::Position(String::from("红方没有帅(将)")));
+    }
+
+    #[test]
+    fn test_load() {
+        let data = load(&parse(ENCRYPTED).unwrap()).unwrap();
+        let main_line: Vec<String> = data.history.iter().map(|s| iccs(s.src, s.dst)).collect();
+        assert_eq!(main_line, ["h2e2", "h9g7", "h0g2", "i9h9"]);
+        assert_eq!(data.current_side, Some(Side::White));
+        assert_eq!(data.broad_map[9][7].unwrap().kind, Kind::Rook);
+
+        let data = load(&parse(ENDGAME).unwrap()).unwrap();
+        assert_eq!(data.history.len(), 2);
+        assert_eq!(data.current_side, Some(Side::Black));
+        assert_eq!(data.broad_map[1][6].unwrap().side, Side::Black);
+        assert_eq!(data.broad_map[2][5].unwrap().kind, Kind::Advisor);
+    }
+}